@@ -0,0 +1,323 @@
+//! Binary GPT backups
+//!
+//! Compatible with `sgdisk --backup` and `sgdisk --load-backup`.
+//!
+//! A backup is the protective MBR, the primary header, and the backup header,
+//! each as a 512 byte record, followed by the partition array.
+use crate::{
+    gpt::{error::*, header::Header},
+    mbr::{ProtectiveMbr, MBR_SIZE},
+    types::*,
+};
+use core::convert::TryInto;
+use crc::crc32;
+use std::io::{prelude::*, SeekFrom};
+
+/// Size of each record in the backup file.
+const RECORD: usize = 512;
+
+/// Offset of the protective partition's size in the MBR.
+const MBR_PART_SIZE: usize = 446 + 12;
+
+/// A backup of a GPT.
+///
+/// Everything is kept as it was on disk, so restoring onto a disk of the
+/// same size is byte for byte identical.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use parts::{backup::Backup, types::*};
+/// # use std::fs::{File, OpenOptions};
+/// # fn main() -> anyhow::Result<()> {
+/// let block_size = BlockSize::new(512);
+/// let disk = File::open("/dev/sda")?;
+/// let backup = Backup::from_device(disk, block_size)?;
+/// backup.to_writer(File::create("sda.gpt")?)?;
+/// // Later
+/// let backup = Backup::from_reader(File::open("sda.gpt")?, block_size)?;
+/// backup.restore(OpenOptions::new().read(true).write(true).open("/dev/sda")?)?;
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backup {
+    block_size: BlockSize,
+    mbr: [u8; RECORD],
+    primary: [u8; RECORD],
+    alt: [u8; RECORD],
+    array: Vec<u8>,
+}
+
+impl Backup {
+    /// Back up the GPT on `source`, a device or disk image.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Invalid`] if the GPT is invalid. Both headers, and the
+    ///   primary partition array, must be intact.
+    /// - [`Error::Io`] if I/O does.
+    pub fn from_device<RS: Read + Seek>(mut source: RS, block_size: BlockSize) -> Result<Self> {
+        let disk_size = Size::from_bytes(source.seek(SeekFrom::End(0))?);
+        let last = (disk_size / block_size) - 1;
+        let mut block = vec![0; block_size.get() as usize];
+        let mut backup = Self {
+            block_size,
+            mbr: [0; RECORD],
+            primary: [0; RECORD],
+            alt: [0; RECORD],
+            array: Vec::new(),
+        };
+        //
+        source.seek(SeekFrom::Start(0))?;
+        source.read_exact(&mut backup.mbr)?;
+        //
+        source.seek(SeekFrom::Start((Block(1) * block_size).0))?;
+        source.read_exact(&mut block)?;
+        let primary = Header::from_bytes(&block, block_size)?;
+        backup.primary.copy_from_slice(&block[..RECORD]);
+        if primary.alt != last {
+            return Err(Error::Invalid("Primary header alternate location invalid"));
+        }
+        //
+        source.seek(SeekFrom::Start((last * block_size).0))?;
+        source.read_exact(&mut block)?;
+        Header::from_bytes(&block, block_size)?;
+        backup.alt.copy_from_slice(&block[..RECORD]);
+        //
+        source.seek(SeekFrom::Start((primary.array * block_size).0))?;
+        (&mut source)
+            .take(array_len(&primary))
+            .read_to_end(&mut backup.array)?;
+        backup.validate()?;
+        Ok(backup)
+    }
+
+    /// Read a backup file, as written by [`Backup::to_writer`] or
+    /// `sgdisk --backup`.
+    ///
+    /// Backups don't record the block size, it must match the disk that was
+    /// backed up.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Invalid`] if the backup is invalid, or any CRC doesn't match.
+    /// - [`Error::Io`] if I/O does.
+    pub fn from_reader<R: Read>(mut source: R, block_size: BlockSize) -> Result<Self> {
+        let mut backup = Self {
+            block_size,
+            mbr: [0; RECORD],
+            primary: [0; RECORD],
+            alt: [0; RECORD],
+            array: Vec::new(),
+        };
+        source.read_exact(&mut backup.mbr)?;
+        source.read_exact(&mut backup.primary)?;
+        source.read_exact(&mut backup.alt)?;
+        let len = array_len(&backup.primary_header()?);
+        source.take(len).read_to_end(&mut backup.array)?;
+        backup.validate()?;
+        Ok(backup)
+    }
+
+    /// Write the backup to `dest`, in the `sgdisk --backup` format.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Io`] if I/O does.
+    pub fn to_writer<W: Write>(&self, mut dest: W) -> Result<()> {
+        dest.write_all(&self.mbr)?;
+        dest.write_all(&self.primary)?;
+        dest.write_all(&self.alt)?;
+        dest.write_all(&self.array)?;
+        dest.flush()?;
+        Ok(())
+    }
+
+    /// Size of the disk that was backed up.
+    pub fn disk_size(&self) -> Size {
+        let primary = self.primary_header().expect("BUG: Backup was validated");
+        ((primary.alt + 1) * self.block_size).into()
+    }
+
+    /// Restore the backup onto `dest`, a device or disk image.
+    ///
+    /// If `dest` is larger than the original disk, the backup header and
+    /// partition array are moved to its end, and the usable range extended
+    /// to match.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::NoSpace`] if `dest` is smaller than the original disk.
+    /// - [`Error::Io`] if I/O does.
+    pub fn restore<WS: Write + Seek>(&self, mut dest: WS) -> Result<()> {
+        let block_size = self.block_size;
+        let disk_size = Size::from_bytes(dest.seek(SeekFrom::End(0))?);
+        if disk_size < self.disk_size() {
+            return Err(Error::NoSpace);
+        }
+        let last = (disk_size / block_size) - 1;
+        let mut primary = self.primary_header()?;
+        let mut alt = self.alt_header()?;
+        let (mut mbr, mut primary_raw, mut alt_raw) = (self.mbr, self.primary, self.alt);
+        if last != primary.alt {
+            let grow = last.0 - primary.alt.0;
+            primary.alt = last;
+            primary.last_usable += grow;
+            alt.this = last;
+            alt.last_usable += grow;
+            alt.array += grow;
+            primary.to_bytes(&mut primary_raw)?;
+            alt.to_bytes(&mut alt_raw)?;
+            // Hybrid MBRs are left alone
+            if ProtectiveMbr::from_bytes(&mbr).is_ok() {
+                let size: u32 = last.0.try_into().unwrap_or(u32::MAX);
+                mbr[MBR_PART_SIZE..][..4].copy_from_slice(&size.to_le_bytes());
+            }
+        }
+        //
+        dest.seek(SeekFrom::Start(0))?;
+        dest.write_all(&mbr[..MBR_SIZE])?;
+        dest.seek(SeekFrom::Start((alt.array * block_size).0))?;
+        dest.write_all(&self.array)?;
+        dest.seek(SeekFrom::Start((alt.this * block_size).0))?;
+        dest.write_all(&alt_raw)?;
+        dest.seek(SeekFrom::Start((primary.array * block_size).0))?;
+        dest.write_all(&self.array)?;
+        dest.seek(SeekFrom::Start((primary.this * block_size).0))?;
+        dest.write_all(&primary_raw)?;
+        dest.flush()?;
+        Ok(())
+    }
+}
+
+// Private APIs
+impl Backup {
+    fn primary_header(&self) -> Result<Header> {
+        Header::from_bytes(&self.primary, self.block_size)
+    }
+
+    fn alt_header(&self) -> Result<Header> {
+        Header::from_bytes(&self.alt, self.block_size)
+    }
+
+    /// Check the headers agree, and the partition array CRC.
+    fn validate(&self) -> Result<()> {
+        let primary = self.primary_header()?;
+        let alt = self.alt_header()?;
+        if primary.this != Block(1) {
+            return Err(Error::Invalid("Primary header location invalid"));
+        }
+        if alt.this != primary.alt || alt.alt != Block(1) {
+            return Err(Error::Invalid("Corrupt Backup GPT Header"));
+        }
+        if self.array.len() as u64 != array_len(&primary) {
+            return Err(Error::NotEnough);
+        }
+        let crc = crc32::checksum_ieee(&self.array);
+        if crc != primary.partitions_crc32 {
+            return Err(Error::Invalid("Primary Partition Array CRC32 mismatch"));
+        }
+        if crc != alt.partitions_crc32 {
+            return Err(Error::Invalid("Backup Partition Array CRC32 mismatch"));
+        }
+        Ok(())
+    }
+}
+
+/// Length of the partition array described by `header`, in bytes.
+fn array_len(header: &Header) -> u64 {
+    u64::from(header.partitions) * u64::from(header.entry_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        util::{Result, BLOCK_SIZE, TEN_MIB_BYTES},
+        Gpt,
+        PartitionBuilder,
+        PartitionType,
+    };
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+    use uuid::Uuid;
+
+    fn disk() -> Result<(Gpt, Cursor<Vec<u8>>)> {
+        let mut gpt: Gpt = Gpt::new(Uuid::from_u128(1), Size::from_mib(10), BLOCK_SIZE);
+        let part = PartitionBuilder::new(Uuid::from_u128(2), &gpt)
+            .start(Block(2048))
+            .size(Size::from_mib(1))
+            .partition_type(PartitionType::LinuxFilesystemData)
+            .name("Root")
+            .finish();
+        gpt.add_partition(part)?;
+        let mut disk = Cursor::new(vec![0; TEN_MIB_BYTES]);
+        gpt.to_writer(&mut disk)?;
+        Ok((gpt, disk))
+    }
+
+    #[test]
+    fn backup_format() -> Result {
+        let (_, disk) = disk()?;
+        let backup = Backup::from_device(disk.clone(), BLOCK_SIZE)?;
+        assert_eq!(backup.disk_size(), Size::from_mib(10));
+        let mut file = Vec::new();
+        backup.to_writer(&mut file)?;
+        //
+        let disk = disk.into_inner();
+        let last = TEN_MIB_BYTES - 512;
+        // The whole 128 entry array
+        let array = 128 * 128;
+        assert_eq!(file.len(), (RECORD * 3) + array);
+        assert_eq!(&file[..1024], &disk[..1024]);
+        assert_eq!(&file[1024..1536], &disk[last..]);
+        assert_eq!(&file[1536..], &disk[1024..][..array]);
+        //
+        assert_eq!(Backup::from_reader(&file[..], BLOCK_SIZE)?, backup);
+        Ok(())
+    }
+
+    #[test]
+    fn restore() -> Result {
+        let (gpt, disk) = disk()?;
+        let backup = Backup::from_device(disk.clone(), BLOCK_SIZE)?;
+        // Same size is identical
+        let mut same = Cursor::new(vec![0; TEN_MIB_BYTES]);
+        backup.restore(&mut same)?;
+        assert!(same.get_ref() == disk.get_ref(), "Restore wasn't identical");
+        // Larger moves the backup header
+        let mut larger = Cursor::new(vec![0; TEN_MIB_BYTES * 2]);
+        backup.restore(&mut larger)?;
+        let restored: Gpt = Gpt::from_reader(&mut larger, BLOCK_SIZE)?;
+        assert_eq!(restored.partitions(), gpt.partitions());
+        assert_eq!(restored.uuid(), gpt.uuid());
+        // Smaller is an error
+        let mut smaller = Cursor::new(vec![0; TEN_MIB_BYTES / 2]);
+        match backup.restore(&mut smaller) {
+            Err(Error::NoSpace) => (),
+            e => panic!("Restored onto a smaller disk: {:?}", e),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn corrupt() -> Result {
+        let (_, disk) = disk()?;
+        let mut file = Vec::new();
+        Backup::from_device(disk, BLOCK_SIZE)?.to_writer(&mut file)?;
+        for (at, truncate) in &[(1536, false), (600, false), (1100, false), (1600, true)] {
+            let mut bad = file.clone();
+            if *truncate {
+                bad.truncate(*at);
+            } else {
+                bad[*at] ^= 0xFF;
+            }
+            assert!(
+                Backup::from_reader(&bad[..], BLOCK_SIZE).is_err(),
+                "Corrupt backup at {} was accepted",
+                at
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+//! Parsing sizes and locations from the command line
+use anyhow::{anyhow, Result};
+use parts::types::*;
+use std::str::FromStr;
+
+/// A location on the disk.
+///
+/// Plain numbers are blocks, anything with a unit is bytes.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Location {
+    Block(u64),
+    Bytes(Size),
+}
+
+impl Location {
+    /// The [`Block`] this refers to.
+    ///
+    /// Byte locations must be a multiple of `block_size`.
+    pub fn block(self, block_size: BlockSize) -> Result<Block> {
+        match self {
+            Location::Block(block) => Ok(Block(block)),
+            Location::Bytes(bytes) if bytes.as_bytes().is_multiple_of(block_size.get()) => {
+                Ok(bytes / block_size)
+            }
+            Location::Bytes(bytes) => Err(anyhow!(
+                "{} is not a multiple of the {} byte block size",
+                bytes.as_bytes(),
+                block_size.get()
+            )),
+        }
+    }
+}
+
+impl FromStr for Location {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match number(s)? {
+            (n, None) => Ok(Location::Block(n)),
+            (n, Some(unit)) => Ok(Location::Bytes(bytes(n, unit)?)),
+        }
+    }
+}
+
+/// The length of a partition.
+///
+/// Plain numbers are blocks, anything with a unit is bytes,
+/// and a `%` is a percentage of the free space.
+///
+/// May be prefixed with `+`, for familiarity with `sgdisk` and `sfdisk`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Length {
+    Blocks(u64),
+    Bytes(Size),
+    Percent(u64),
+}
+
+impl Length {
+    /// Number of blocks this refers to, given `free` blocks of free space.
+    ///
+    /// Byte lengths are rounded up to whole blocks,
+    /// percentages are rounded down.
+    pub fn blocks(self, block_size: BlockSize, free: u64) -> u64 {
+        match self {
+            Length::Blocks(blocks) => blocks,
+            Length::Bytes(bytes) => bytes.as_bytes().div_ceil(block_size.get()),
+            Length::Percent(percent) => free * percent / 100,
+        }
+    }
+}
+
+impl FromStr for Length {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.strip_prefix('+').unwrap_or(s);
+        if let Some(percent) = s.strip_suffix('%') {
+            let percent: u64 = percent.trim().parse()?;
+            if percent == 0 || percent > 100 {
+                return Err(anyhow!("Percentage must be between 1 and 100"));
+            }
+            return Ok(Length::Percent(percent));
+        }
+        match number(s)? {
+            (n, None) => Ok(Length::Blocks(n)),
+            (n, Some(unit)) => Ok(Length::Bytes(bytes(n, unit)?)),
+        }
+    }
+}
+
+/// Parse a partition name, which must fit in a GPT entry.
+pub fn parse_name(s: &str) -> Result<String> {
+    if s.len() > 70 {
+        return Err(anyhow!("Partition names must be at most 70 bytes"));
+    }
+    Ok(s.into())
+}
+
+/// Split `s` into a number and an optional unit.
+fn number(s: &str) -> Result<(u64, Option<&str>)> {
+    let s = s.trim();
+    let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let n = s[..digits]
+        .parse()
+        .map_err(|_| anyhow!("Expected a number, got {:?}", s))?;
+    let unit = s[digits..].trim();
+    Ok((n, if unit.is_empty() { None } else { Some(unit) }))
+}
+
+/// `n` of `unit`, in bytes.
+fn bytes(n: u64, unit: &str) -> Result<Size> {
+    let shift = match unit {
+        "B" => 0,
+        "K" | "KiB" => 10,
+        "M" | "MiB" => 20,
+        "G" | "GiB" => 30,
+        "T" | "TiB" => 40,
+        "P" | "PiB" => 50,
+        _ => return Err(anyhow!("Unknown unit {:?}", unit)),
+    };
+    n.checked_mul(1 << shift)
+        .map(Size::from_bytes)
+        .ok_or_else(|| anyhow!("{}{} is too large", n, unit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() -> Result<()> {
+        let block_size = BlockSize::new(512);
+        assert_eq!("2048".parse::<Location>()?, Location::Block(2048));
+        assert_eq!("1MiB".parse::<Location>()?.block(block_size)?, Block(2048));
+        assert!("100B".parse::<Location>()?.block(block_size).is_err());
+        //
+        assert_eq!(
+            "512MiB".parse::<Length>()?,
+            Length::Bytes(Size::from_mib(512))
+        );
+        assert_eq!("+1 G".parse::<Length>()?, Length::Bytes(Size::from_gib(1)));
+        assert_eq!("+100%".parse::<Length>()?, Length::Percent(100));
+        assert_eq!("8".parse::<Length>()?, Length::Blocks(8));
+        assert_eq!("1000B".parse::<Length>()?.blocks(block_size, 0), 2);
+        assert_eq!("50%".parse::<Length>()?.blocks(block_size, 101), 50);
+        //
+        for bad in &["", "MiB", "1XiB", "0%", "101%", "99999999999999999999P"] {
+            assert!(bad.parse::<Length>().is_err(), "{:?} was accepted", bad);
+        }
+        Ok(())
+    }
+}
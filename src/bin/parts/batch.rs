@@ -0,0 +1,215 @@
+//! The `batch` command, running a script of edits atomically
+//!
+//! Scripts have one edit per line, written exactly like the command line
+//! subcommand, without the device. Blank lines and lines starting with `#`
+//! are ignored.
+//!
+//! ```text
+//! # Start fresh
+//! delete 1
+//! add --size 512MiB --type EfiSystem --name EFI
+//! add --size +100% --name "Linux root"
+//! set-attr 2 63
+//! ```
+//!
+//! Partition numbers refer to the table as it is after the previous lines.
+use crate::{
+    device::{validate, Device},
+    edit::{Add, Delete, Operation, Resize, SetAttr, SetName, SetType},
+    list::table,
+};
+use anyhow::{anyhow, Context, Result};
+use parts::Gpt;
+use std::{
+    fs,
+    io::{self, prelude::*},
+    path::PathBuf,
+};
+use structopt::{clap::AppSettings, StructOpt};
+
+#[derive(Debug, StructOpt)]
+pub struct Batch {
+    #[structopt(flatten)]
+    device: Device,
+
+    /// Script to run, or `-` to read it from stdin
+    #[structopt(parse(from_os_str))]
+    script: PathBuf,
+
+    /// Check the script and print the resulting table and the writes that
+    /// would be made, without writing anything
+    #[structopt(long)]
+    dry_run: bool,
+}
+
+impl Batch {
+    pub fn run(&self) -> Result<()> {
+        let script = if self.script.as_os_str() == "-" {
+            let mut script = String::new();
+            io::stdin().read_to_string(&mut script)?;
+            script
+        } else {
+            fs::read_to_string(&self.script)
+                .with_context(|| format!("Couldn't read {}", self.script.display()))?
+        };
+        let mut gpt = self.device.read()?;
+        run_script(&mut gpt, &script)?;
+        if self.dry_run {
+            let stdout = io::stdout();
+            let mut out = stdout.lock();
+            table(&gpt, &self.device.name(), &mut out)?;
+            writeln!(out, "\nWrites:")?;
+            for write in gpt.write_plan(gpt.block_size(), gpt.disk_size())? {
+                writeln!(out, "  {}", write)?;
+            }
+            return Ok(());
+        }
+        self.device.write(&gpt)
+    }
+}
+
+/// An edit on one line of a script.
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "script",
+    setting = AppSettings::NoBinaryName,
+    setting = AppSettings::ColorNever
+)]
+enum Line {
+    Add(Add),
+    Delete(Delete),
+    Resize(Resize),
+    SetName(SetName),
+    SetType(SetType),
+    SetAttr(SetAttr),
+}
+
+impl Line {
+    fn op(&self) -> &dyn Operation {
+        match self {
+            Line::Add(op) => op,
+            Line::Delete(op) => op,
+            Line::Resize(op) => op,
+            Line::SetName(op) => op,
+            Line::SetType(op) => op,
+            Line::SetAttr(op) => op,
+        }
+    }
+}
+
+/// Run every line of `script` on `gpt`, then validate the result.
+///
+/// `gpt` is only changed if the whole script succeeded.
+pub fn run_script(gpt: &mut Gpt, script: &str) -> Result<()> {
+    let mut new = gpt.clone();
+    for (i, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let err = || format!("Line {}: {}", i + 1, line);
+        let op = Line::from_iter_safe(words(line).with_context(err)?).map_err(|e| {
+            // Only the first line, the rest is usage.
+            let msg = e.message.lines().next().unwrap_or_default();
+            anyhow!("{}", msg.trim_start_matches("error: ")).context(err())
+        })?;
+        op.op().apply(&mut new).with_context(err)?;
+    }
+    validate(&new)?;
+    *gpt = new;
+    Ok(())
+}
+
+/// Split `line` into words, like a shell.
+///
+/// Words are separated by whitespace, and may be quoted with `"` or `'`.
+/// A `\` escapes the next character, outside of single quotes.
+fn words(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', q) if q != Some('\'') => {
+                let c = chars.next().ok_or_else(|| anyhow!("Trailing backslash"))?;
+                word.get_or_insert_with(String::new).push(c);
+            }
+            (c, Some(q)) if c == q => quote = None,
+            (c, Some(_)) => word.get_or_insert_with(String::new).push(c),
+            ('"', None) | ('\'', None) => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (c, None) if c.is_whitespace() => words.extend(word.take()),
+            (c, None) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(anyhow!("Unterminated quote"));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parts::{types::*, uuid::Uuid, PartitionType};
+
+    fn gpt() -> Gpt {
+        Gpt::new(Uuid::nil(), Size::from_mib(10), BlockSize::new(512))
+    }
+
+    #[test]
+    fn split() -> Result<()> {
+        assert_eq!(
+            words(r#"set-name 1 "Linux root" 'a b' c\ d "" e"#)?,
+            ["set-name", "1", "Linux root", "a b", "c d", "", "e"]
+        );
+        assert!(words(r#"set-name 1 "Linux"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn script() -> Result<()> {
+        let mut gpt = gpt();
+        let script = r#"
+            # A comment
+            add --size 1MiB --type EfiSystem --name EFI
+            add --size +100% --name "Linux root"
+            resize 2 2MiB
+            set-type 2 LinuxLvm
+            set-attr 1 0 63
+            set-attr 1 --clear 63
+        "#;
+        run_script(&mut gpt, script)?;
+        let parts = gpt.partitions();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].partition_type(), PartitionType::EfiSystem);
+        assert_eq!(parts[0].attributes(), 1);
+        assert_eq!(parts[1].name(), "Linux root");
+        assert_eq!(parts[1].partition_type(), PartitionType::LinuxLvm);
+        assert_eq!(gpt.partition_size(&parts[1]), Size::from_mib(2));
+        Ok(())
+    }
+
+    #[test]
+    fn atomic() -> Result<()> {
+        let mut gpt = gpt();
+        run_script(&mut gpt, "add --size 1MiB")?;
+        let before = gpt.clone();
+        for script in &[
+            "delete 1\ndelete 1",
+            "delete 1\nbogus",
+            "delete 1\nadd --size 1MiB --type Unused",
+            "set-name 1 'unterminated",
+            "resize 1 20MiB",
+        ] {
+            let err = run_script(&mut gpt, script).unwrap_err();
+            assert!(err.to_string().starts_with("Line "), "{}", err);
+            assert_eq!(gpt, before, "Script {:?} partially applied", script);
+        }
+        Ok(())
+    }
+}
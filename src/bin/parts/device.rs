@@ -0,0 +1,147 @@
+//! Opening devices and disk images
+use anyhow::{anyhow, Context, Result};
+use parts::{hybrid, types::*, Gpt};
+use std::{
+    fs::{File, OpenOptions},
+    io::{prelude::*, SeekFrom},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+/// The device or disk image to operate on.
+#[derive(Debug, StructOpt)]
+pub struct Device {
+    /// Device or disk image
+    #[structopt(parse(from_os_str))]
+    pub path: PathBuf,
+
+    /// Block size, in bytes. Detected from the GPT if not given.
+    #[structopt(long, short, parse(try_from_str = parse_block_size))]
+    pub block_size: Option<BlockSize>,
+}
+
+impl Device {
+    /// Name of the device, for display.
+    pub fn name(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    /// Open the device read only.
+    pub fn open(&self) -> Result<File> {
+        File::open(&self.path).with_context(|| format!("Couldn't open {}", self.name()))
+    }
+
+    /// Open the device for reading and writing.
+    pub fn open_rw(&self) -> Result<File> {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.path)
+            .with_context(|| format!("Couldn't open {} for writing", self.name()))
+    }
+
+    /// Read the GPT on the device.
+    pub fn read(&self) -> Result<Gpt> {
+        let mut file = self.open()?;
+        let block_size = self.block_size(&mut file)?;
+        let gpt = Gpt::from_reader(&mut file, block_size)
+            .with_context(|| format!("Couldn't read the GPT on {}", self.name()))?;
+        Ok(gpt)
+    }
+
+    /// Write `gpt` to the device, waiting for each copy to reach the disk.
+    ///
+    /// Nothing is written if `gpt` is invalid, and only blocks that change
+    /// are written otherwise.
+    pub fn write(&self, gpt: &Gpt) -> Result<()> {
+        validate(gpt)?;
+        let file = self.open_rw()?;
+        let disk_size = Size::from_bytes((&file).seek(SeekFrom::End(0))?);
+        gpt.to_bytes_incremental_with_func(
+            |offset, buf| {
+                let mut file = &file;
+                file.seek(SeekFrom::Start(offset.0))?;
+                file.read_exact(buf)?;
+                Ok(())
+            },
+            |offset, buf| {
+                let mut file = &file;
+                file.seek(SeekFrom::Start(offset.0))?;
+                file.write_all(buf)?;
+                Ok(())
+            },
+            || Ok(file.sync_data()?),
+            gpt.block_size(),
+            disk_size,
+        )
+        .with_context(|| format!("Couldn't write the GPT to {}", self.name()))?;
+        Ok(())
+    }
+
+    /// Size of the device, in bytes.
+    pub fn size(&self) -> Result<Size> {
+        let mut file = self.open()?;
+        Ok(Size::from_bytes(file.seek(SeekFrom::End(0))?))
+    }
+
+    /// The block size given, or detected from `file`.
+    pub fn block_size(&self, file: &mut File) -> Result<BlockSize> {
+        if let Some(block_size) = self.block_size {
+            return Ok(block_size);
+        }
+        let disk_size = Size::from_bytes(file.seek(SeekFrom::End(0))?);
+        let block_size = hybrid::detect_block_size(
+            |i, buf| {
+                file.seek(SeekFrom::Start(i.0))?;
+                file.read_exact(buf)?;
+                Ok(())
+            },
+            disk_size,
+        )
+        .with_context(|| format!("Couldn't detect the block size of {}", self.name()))?;
+        Ok(block_size)
+    }
+}
+
+/// Check `gpt` is valid before writing it, see [`Gpt::validate`].
+pub fn validate(gpt: &Gpt) -> Result<()> {
+    gpt.validate().context("Invalid GPT")
+}
+
+/// Parse a block size, which must be a power of two and at least 512.
+fn parse_block_size(s: &str) -> Result<BlockSize> {
+    let size: u64 = s.parse()?;
+    if size < 512 || !size.is_power_of_two() {
+        return Err(anyhow!(
+            "Block size must be a power of two, and at least 512"
+        ));
+    }
+    Ok(BlockSize::new(size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parts::{uuid::Uuid, PartitionBuilder, PartitionType};
+
+    #[test]
+    fn validate_bounds() -> Result<()> {
+        let mut gpt: Gpt = Gpt::new(Uuid::nil(), Size::from_mib(10), BlockSize::new(512));
+        let part = PartitionBuilder::new(Uuid::from_u128(1), &gpt)
+            .start(Block(2048))
+            .size(Size::from_mib(1))
+            .partition_type(PartitionType::LinuxFilesystemData)
+            .finish();
+        gpt.add_partition(part)?;
+        validate(&gpt)?;
+        // Past the end of the disk
+        let part = PartitionBuilder::new(Uuid::from_u128(2), &gpt)
+            .start(Block(8192))
+            .size(Size::from_mib(8))
+            .partition_type(PartitionType::LinuxFilesystemData)
+            .finish();
+        assert!(gpt.add_partition(part).is_err());
+        validate(&gpt)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,299 @@
+//! Commands that create and edit partition tables
+//!
+//! Every edit is an [`Operation`] on an in-memory [`Gpt`], which [`Edit`]
+//! runs on a device, writing only if the change was valid.
+use crate::{
+    args::{parse_name, Length, Location},
+    device::Device,
+};
+use anyhow::{anyhow, bail, Result};
+use parts::{types::*, uuid::Uuid, Gpt, PartitionBuilder, PartitionType};
+use structopt::StructOpt;
+
+/// Create a new, empty, GPT
+#[derive(Debug, StructOpt)]
+pub struct New {
+    #[structopt(flatten)]
+    device: Device,
+
+    /// Disk GUID. Random if not given.
+    #[structopt(long, short)]
+    uuid: Option<Uuid>,
+
+    /// Overwrite an existing GPT
+    #[structopt(long, short)]
+    force: bool,
+}
+
+impl New {
+    pub fn run(&self) -> Result<()> {
+        if !self.force && self.device.read().is_ok() {
+            bail!(
+                "{} already has a GPT, use --force to overwrite it",
+                self.device.name()
+            );
+        }
+        let block_size = self
+            .device
+            .block_size
+            .unwrap_or_else(|| BlockSize::new(512));
+        let disk_size = self.device.size()?;
+        // The MBR, both headers and 16 KiB partition arrays, and a usable block
+        let min = block_size.get() * 4 + 16384 * 2;
+        if disk_size.as_bytes() < min {
+            bail!(
+                "{} is too small for a GPT, it must be at least {} bytes",
+                self.device.name(),
+                min
+            );
+        }
+        let uuid = self.uuid.unwrap_or_else(Uuid::new_v4);
+        let gpt = Gpt::new(uuid, disk_size, block_size);
+        self.device.write(&gpt)
+    }
+}
+
+/// An edit to a [`Gpt`], in memory.
+pub trait Operation {
+    /// Apply the edit to `gpt`.
+    ///
+    /// On error `gpt` may have been partially modified.
+    fn apply(&self, gpt: &mut Gpt) -> Result<()>;
+}
+
+/// Run the [`Operation`] `T` on a device.
+#[derive(Debug, StructOpt)]
+pub struct Edit<T: StructOpt> {
+    #[structopt(flatten)]
+    device: Device,
+
+    #[structopt(flatten)]
+    op: T,
+}
+
+impl<T: StructOpt + Operation> Edit<T> {
+    pub fn run(&self) -> Result<()> {
+        let mut gpt = self.device.read()?;
+        self.op.apply(&mut gpt)?;
+        self.device.write(&gpt)
+    }
+}
+
+/// Add a partition
+#[derive(Debug, StructOpt)]
+pub struct Add {
+    /// Start of the partition, in blocks or with a unit, like `1MiB`.
+    ///
+    /// Defaults to the first 1MiB aligned block after all partitions.
+    #[structopt(long)]
+    start: Option<Location>,
+
+    /// Size of the partition, in blocks, with a unit, like `512MiB`,
+    /// or as a percentage of the free space after the start, like `+100%`.
+    #[structopt(long, short, default_value = "+100%")]
+    size: Length,
+
+    /// Partition type, by name or GUID
+    #[structopt(long = "type", short = "t", default_value = "LinuxFilesystemData")]
+    partition_type: PartitionType,
+
+    /// Partition name
+    #[structopt(long, short, default_value = "", parse(try_from_str = parse_name))]
+    name: String,
+
+    /// Partition GUID. Random if not given.
+    #[structopt(long, short)]
+    uuid: Option<Uuid>,
+}
+
+impl Operation for Add {
+    fn apply(&self, gpt: &mut Gpt) -> Result<()> {
+        let block_size = gpt.block_size();
+        let start = match self.start {
+            Some(start) => start.block(block_size)?,
+            None => gpt.next_usable_aligned(),
+        };
+        let free = (limit(gpt, start).0 + 1).saturating_sub(start.0);
+        let blocks = self.size.blocks(block_size, free);
+        if blocks == 0 {
+            bail!("No space for a partition at {}", start);
+        }
+        if self.partition_type == PartitionType::Unused {
+            bail!("Partition type must not be Unused");
+        }
+        let uuid = self.uuid.unwrap_or_else(Uuid::new_v4);
+        if gpt.find_by_uuid(uuid).is_some() {
+            bail!("Partition GUID {} is already in use", uuid);
+        }
+        let part = PartitionBuilder::new(uuid, gpt)
+            .start(start)
+            .end(start + (blocks - 1))
+            .partition_type(self.partition_type)
+            .name(&self.name)
+            .finish();
+        gpt.add_partition(part)?;
+        Ok(())
+    }
+}
+
+/// Delete a partition
+#[derive(Debug, StructOpt)]
+pub struct Delete {
+    /// Partition number, as shown by `list`
+    partition: usize,
+}
+
+impl Operation for Delete {
+    fn apply(&self, gpt: &mut Gpt) -> Result<()> {
+        let index = index(gpt, self.partition)?;
+        gpt.remove_partition(index);
+        Ok(())
+    }
+}
+
+/// Resize a partition, keeping its start
+#[derive(Debug, StructOpt)]
+pub struct Resize {
+    /// Partition number, as shown by `list`
+    partition: usize,
+
+    /// New size, in blocks, with a unit, like `512MiB`, or as a percentage
+    /// of the space until the next partition, like `+100%`.
+    size: Length,
+}
+
+impl Operation for Resize {
+    fn apply(&self, gpt: &mut Gpt) -> Result<()> {
+        let index = index(gpt, self.partition)?;
+        let start = gpt.partitions()[index].start();
+        let free = limit(gpt, start).0 - start.0 + 1;
+        let blocks = self.size.blocks(gpt.block_size(), free);
+        if blocks == 0 {
+            bail!("Partition size must not be zero");
+        }
+        gpt.resize_partition(index, start + (blocks - 1))?;
+        Ok(())
+    }
+}
+
+/// Set the name of a partition
+#[derive(Debug, StructOpt)]
+pub struct SetName {
+    /// Partition number, as shown by `list`
+    partition: usize,
+
+    /// New name
+    #[structopt(parse(try_from_str = parse_name))]
+    name: String,
+}
+
+impl Operation for SetName {
+    fn apply(&self, gpt: &mut Gpt) -> Result<()> {
+        let index = index(gpt, self.partition)?;
+        gpt.modify_partition(index, |p| p.set_name(&self.name))?;
+        Ok(())
+    }
+}
+
+/// Set the type of a partition
+#[derive(Debug, StructOpt)]
+pub struct SetType {
+    /// Partition number, as shown by `list`
+    partition: usize,
+
+    /// New type, by name or GUID
+    #[structopt(name = "type")]
+    partition_type: PartitionType,
+}
+
+impl Operation for SetType {
+    fn apply(&self, gpt: &mut Gpt) -> Result<()> {
+        let index = index(gpt, self.partition)?;
+        gpt.modify_partition(index, |p| p.set_partition_type(self.partition_type))?;
+        Ok(())
+    }
+}
+
+/// Set, or clear, attribute bits of a partition
+#[derive(Debug, StructOpt)]
+pub struct SetAttr {
+    /// Partition number, as shown by `list`
+    partition: usize,
+
+    /// Attribute bits, 0 to 63
+    #[structopt(required = true)]
+    bits: Vec<u8>,
+
+    /// Clear the bits instead of setting them
+    #[structopt(long, short)]
+    clear: bool,
+}
+
+impl Operation for SetAttr {
+    fn apply(&self, gpt: &mut Gpt) -> Result<()> {
+        let index = index(gpt, self.partition)?;
+        let mut mask = 0u64;
+        for &bit in &self.bits {
+            if bit >= 64 {
+                bail!("Attribute bit {} is out of range", bit);
+            }
+            mask |= 1 << bit;
+        }
+        gpt.modify_partition(index, |p| {
+            let attrs = p.attributes();
+            p.set_attributes(if self.clear {
+                attrs & !mask
+            } else {
+                attrs | mask
+            });
+        })?;
+        Ok(())
+    }
+}
+
+/// The last block of free space starting at `start`, before the next
+/// partition or the end of the disk.
+fn limit(gpt: &Gpt, start: Block) -> Block {
+    gpt.partitions()
+        .iter()
+        .map(|p| p.start())
+        .filter(|s| *s > start)
+        .min()
+        .map_or_else(|| gpt.last_usable(), |s| s - 1)
+}
+
+/// Index of the partition numbered `number`, as shown by `list`.
+fn index(gpt: &Gpt, number: usize) -> Result<usize> {
+    number
+        .checked_sub(1)
+        .and_then(|slot| gpt.index_of_slot(slot))
+        .ok_or_else(|| anyhow!("No partition {}", number))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+
+    #[test]
+    fn new_too_small() -> Result<()> {
+        let path = env::temp_dir().join(format!("parts-new-{}", std::process::id()));
+        fs::write(&path, [])?;
+        let new = New {
+            device: Device {
+                path: path.clone(),
+                block_size: None,
+            },
+            uuid: None,
+            force: false,
+        };
+        let err = new.run();
+        // Just big enough
+        fs::write(&path, vec![0; 512 * 4 + 16384 * 2])?;
+        let ok = new.run().and_then(|_| new.device.read());
+        fs::remove_file(&path)?;
+        assert!(err.unwrap_err().to_string().contains("too small"));
+        assert!(ok?.partitions().is_empty());
+        Ok(())
+    }
+}
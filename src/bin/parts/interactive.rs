@@ -0,0 +1,298 @@
+//! The `interactive` command, a full screen partition editor
+//!
+//! Similar to `cfdisk`. All changes are made in memory, and only written
+//! after explicit confirmation.
+use crate::{
+    args::{parse_name, Length},
+    device::Device,
+    list::{human, upper},
+};
+use anyhow::Result;
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{read, Event, KeyCode, KeyEvent, KeyModifiers},
+    execute,
+    queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{
+        disable_raw_mode,
+        enable_raw_mode,
+        Clear,
+        ClearType,
+        EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
+};
+use editor::{number, Editor, Row};
+use parts::{types::Size, PartitionType};
+use std::io::{self, prelude::*, Stdout};
+use structopt::StructOpt;
+
+mod editor;
+
+#[derive(Debug, StructOpt)]
+pub struct Interactive {
+    #[structopt(flatten)]
+    device: Device,
+}
+
+impl Interactive {
+    pub fn run(&self) -> Result<()> {
+        let gpt = self.device.read()?;
+        let mut ui = Ui {
+            device: &self.device,
+            editor: Editor::new(gpt),
+            status: String::new(),
+            out: Terminal::new()?,
+        };
+        ui.run()
+    }
+}
+
+/// Puts the terminal back how it was when dropped, even on errors.
+struct Terminal(Stdout);
+
+impl Terminal {
+    fn new() -> Result<Self> {
+        let mut out = io::stdout();
+        execute!(out, EnterAlternateScreen, Hide)?;
+        enable_raw_mode()?;
+        Ok(Self(out))
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(self.0, Show, LeaveAlternateScreen);
+    }
+}
+
+struct Ui<'a> {
+    device: &'a Device,
+    editor: Editor,
+    /// Result of the last action
+    status: String,
+    out: Terminal,
+}
+
+impl Ui<'_> {
+    fn run(&mut self) -> Result<()> {
+        loop {
+            self.draw()?;
+            let key = match read()? {
+                Event::Key(key) => key,
+                _ => continue,
+            };
+            self.status.clear();
+            let result = match key.code {
+                KeyCode::Up | KeyCode::Char('k') => {
+                    self.editor.up();
+                    Ok(())
+                }
+                KeyCode::Down | KeyCode::Char('j') => {
+                    self.editor.down();
+                    Ok(())
+                }
+                KeyCode::Char('n') => self.create(),
+                KeyCode::Char('d') => self.editor.delete(),
+                KeyCode::Char('r') => self.resize(),
+                KeyCode::Char('t') => self.set_type(),
+                KeyCode::Char('m') => self.set_name(),
+                KeyCode::Char('w') => self.write(),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(())
+                }
+                KeyCode::Char('q') => {
+                    if self.quit()? {
+                        return Ok(());
+                    }
+                    Ok(())
+                }
+                _ => Ok(()),
+            };
+            if let Err(e) = result {
+                self.status = e.to_string();
+            }
+        }
+    }
+
+    fn create(&mut self) -> Result<()> {
+        if !matches!(self.editor.selected_row(), Some(Row::Free { .. })) {
+            self.status = "Select free space to create a partition".into();
+            return Ok(());
+        }
+        let size = match self.prompt("Size (e.g. 512MiB, 100%)", "100%")? {
+            Some(size) => size.parse::<Length>()?,
+            None => return Ok(()),
+        };
+        let p_type = match self.prompt("Type (name or GUID)", "LinuxFilesystemData")? {
+            Some(p_type) => p_type.parse::<PartitionType>()?,
+            None => return Ok(()),
+        };
+        let name = match self.prompt("Name", "")? {
+            Some(name) => parse_name(&name)?,
+            None => return Ok(()),
+        };
+        self.editor.create(size, p_type, &name)
+    }
+
+    fn resize(&mut self) -> Result<()> {
+        if let Some(size) = self.prompt("New size (e.g. 512MiB, 100%)", "100%")? {
+            self.editor.resize(size.parse()?)?;
+        }
+        Ok(())
+    }
+
+    fn set_type(&mut self) -> Result<()> {
+        if let Some(p_type) = self.prompt("New type (name or GUID)", "")? {
+            self.editor.set_type(p_type.parse()?)?;
+        }
+        Ok(())
+    }
+
+    fn set_name(&mut self) -> Result<()> {
+        if let Some(name) = self.prompt("New name", "")? {
+            self.editor.set_name(&parse_name(&name)?)?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self) -> Result<()> {
+        if !self.editor.is_modified() {
+            self.status = "No changes to write".into();
+            return Ok(());
+        }
+        let answer = self.prompt("Write the partition table to disk? Type \"yes\"", "")?;
+        if answer.as_deref() != Some("yes") {
+            self.status = "Nothing was written".into();
+            return Ok(());
+        }
+        self.device.write(self.editor.gpt())?;
+        self.editor.saved();
+        self.status = "The partition table was written".into();
+        Ok(())
+    }
+
+    /// Whether it's okay to quit.
+    fn quit(&mut self) -> Result<bool> {
+        if !self.editor.is_modified() {
+            return Ok(true);
+        }
+        let answer = self.prompt("Discard unwritten changes? Type \"yes\"", "")?;
+        Ok(answer.as_deref() == Some("yes"))
+    }
+
+    /// Ask for a line of input. `None` if cancelled with escape.
+    fn prompt(&mut self, question: &str, default: &str) -> Result<Option<String>> {
+        let mut input = String::from(default);
+        loop {
+            self.status = format!("{}: {}_", question, input);
+            self.draw()?;
+            match read()? {
+                Event::Key(KeyEvent { code, .. }) => match code {
+                    KeyCode::Enter => break,
+                    KeyCode::Esc => {
+                        self.status.clear();
+                        return Ok(None);
+                    }
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    KeyCode::Char(c) => input.push(c),
+                    _ => (),
+                },
+                _ => continue,
+            }
+        }
+        self.status.clear();
+        Ok(Some(input))
+    }
+
+    fn draw(&mut self) -> Result<()> {
+        let lines = screen(&self.editor, &self.device.name(), &self.status);
+        let out = &mut self.out.0;
+        queue!(out, Clear(ClearType::All))?;
+        for (y, (line, highlight)) in lines.iter().enumerate() {
+            queue!(out, MoveTo(0, y as u16))?;
+            if *highlight {
+                queue!(
+                    out,
+                    SetAttribute(Attribute::Reverse),
+                    Print(line),
+                    SetAttribute(Attribute::Reset)
+                )?;
+            } else {
+                queue!(out, Print(line))?;
+            }
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// The lines of the screen, and whether they're highlighted.
+fn screen(editor: &Editor, device: &str, status: &str) -> Vec<(String, bool)> {
+    let gpt = editor.gpt();
+    let block_size = gpt.block_size();
+    let mut lines = vec![
+        (format!("Disk: {}", device), false),
+        (
+            format!(
+                "Size: {}, {} sectors of {} bytes",
+                human(gpt.disk_size()),
+                (gpt.disk_size() / block_size).0,
+                block_size.get()
+            ),
+            false,
+        ),
+        (format!("Disk identifier: {}", upper(gpt.uuid())), false),
+        (String::new(), false),
+        (
+            format!(
+                "{:>3}  {:>12}  {:>12}  {:>10}  {:<20}  {}",
+                "#", "Start", "End", "Size", "Type", "Name"
+            ),
+            false,
+        ),
+    ];
+    for (i, row) in editor.rows().into_iter().enumerate() {
+        let text = match row {
+            Row::Partition(index) => {
+                let part = gpt.partitions()[index];
+                format!(
+                    "{:>3}  {:>12}  {:>12}  {:>10}  {:<20}  {}",
+                    number(&part),
+                    part.start().0,
+                    part.end().0,
+                    human(gpt.partition_size(&part)),
+                    part.partition_type().to_string(),
+                    part.name()
+                )
+            }
+            Row::Free { start, end } => format!(
+                "{:>3}  {:>12}  {:>12}  {:>10}  {:<20}",
+                "",
+                start.0,
+                end.0,
+                human(Size::from_bytes((end.0 - start.0 + 1) * block_size.get())),
+                "Free space"
+            ),
+        };
+        lines.push((text, i == editor.selected()));
+    }
+    lines.push((String::new(), false));
+    if !editor.changes().is_empty() {
+        lines.push(("Pending changes:".into(), false));
+        for change in editor.changes() {
+            lines.push((format!("  {}", change), false));
+        }
+        lines.push((String::new(), false));
+    }
+    lines.push((
+        "[n]ew  [d]elete  [r]esize  [t]ype  na[m]e  [w]rite  [q]uit".into(),
+        false,
+    ));
+    lines.push((status.into(), false));
+    lines
+}
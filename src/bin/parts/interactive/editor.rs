@@ -0,0 +1,310 @@
+//! Editor state for the interactive UI, independent of the terminal
+use crate::{args::Length, list::human};
+use anyhow::{anyhow, bail, Result};
+use parts::{types::*, uuid::Uuid, Gpt, Partition, PartitionBuilder, PartitionType};
+
+/// A row in the editor, either a partition or free space.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Row {
+    /// Index of the partition
+    Partition(usize),
+
+    /// Free space, inclusive.
+    Free { start: Block, end: Block },
+}
+
+/// Number of `part`, as shown to the user.
+///
+/// This is its slot plus one, the same as the kernel's partition number.
+pub fn number(part: &Partition) -> usize {
+    part.slot().expect("BUG: Partition without a slot") + 1
+}
+
+/// An in-memory [`Gpt`] being edited, and the changes made to it.
+///
+/// Every change goes through the [`Gpt`] methods, so the table is always
+/// valid, but nothing is written until [`Editor::saved`].
+#[derive(Debug)]
+pub struct Editor {
+    gpt: Gpt,
+    original: Gpt,
+    selected: usize,
+    changes: Vec<String>,
+}
+
+impl Editor {
+    pub fn new(gpt: Gpt) -> Self {
+        Self {
+            original: gpt.clone(),
+            gpt,
+            selected: 0,
+            changes: Vec::new(),
+        }
+    }
+
+    pub fn gpt(&self) -> &Gpt {
+        &self.gpt
+    }
+
+    /// Descriptions of the changes not yet written.
+    pub fn changes(&self) -> &[String] {
+        &self.changes
+    }
+
+    /// Whether the table differs from what is on disk.
+    pub fn is_modified(&self) -> bool {
+        self.gpt != self.original
+    }
+
+    /// The table was written, there are no more pending changes.
+    pub fn saved(&mut self) {
+        self.original = self.gpt.clone();
+        self.changes.clear();
+    }
+
+    /// Partitions and free space, in disk order.
+    ///
+    /// Free space too small to hold an aligned partition is not shown.
+    pub fn rows(&self) -> Vec<Row> {
+        let mut rows = Vec::new();
+        let mut next = self.gpt.first_usable();
+        for (i, part) in self.gpt.partitions().iter().enumerate() {
+            if part.start() > next {
+                self.push_free(&mut rows, next, part.start() - 1);
+            }
+            rows.push(Row::Partition(i));
+            next = part.end() + 1;
+        }
+        self.push_free(&mut rows, next, self.gpt.last_usable());
+        rows
+    }
+
+    /// Index of the selected row.
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected_row(&self) -> Option<Row> {
+        self.rows().get(self.selected).copied()
+    }
+
+    pub fn up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn down(&mut self) {
+        if self.selected + 1 < self.rows().len() {
+            self.selected += 1;
+        }
+    }
+
+    /// Create a partition in the selected free space.
+    pub fn create(&mut self, length: Length, p_type: PartitionType, name: &str) -> Result<()> {
+        let (start, end) = match self.selected_row() {
+            Some(Row::Free { start, end }) => (self.align(start), end),
+            _ => bail!("Select free space to create a partition"),
+        };
+        let free = end.0 - start.0 + 1;
+        let blocks = length.blocks(self.gpt.block_size(), free);
+        if blocks == 0 || blocks > free {
+            bail!("Not enough free space, only {} available", self.size(free));
+        }
+        if p_type == PartitionType::Unused {
+            bail!("Partition type must not be Unused");
+        }
+        let part = PartitionBuilder::new(Uuid::new_v4(), &self.gpt)
+            .start(start)
+            .end(start + (blocks - 1))
+            .partition_type(p_type)
+            .name(name)
+            .finish();
+        self.gpt.add_partition(part)?;
+        let index = self.index_of(part.uuid());
+        self.changes.push(format!(
+            "Create partition {}, {} of {} at block {}",
+            number(&self.gpt.partitions()[index]),
+            self.size(blocks),
+            p_type,
+            start.0
+        ));
+        self.select_partition(index);
+        Ok(())
+    }
+
+    /// Delete the selected partition.
+    pub fn delete(&mut self) -> Result<()> {
+        let index = self.partition()?;
+        let part = self.gpt.remove_partition(index);
+        self.changes.push(format!(
+            "Delete partition {} {:?}",
+            number(&part),
+            part.name()
+        ));
+        self.selected = self.selected.min(self.rows().len().saturating_sub(1));
+        Ok(())
+    }
+
+    /// Resize the selected partition to `length`.
+    ///
+    /// Percentages are of the space from the partition start to the next
+    /// partition, or the end of the disk.
+    pub fn resize(&mut self, length: Length) -> Result<()> {
+        let index = self.partition()?;
+        let part = self.gpt.partitions()[index];
+        let limit = self
+            .gpt
+            .partitions()
+            .get(index + 1)
+            .map_or_else(|| self.gpt.last_usable(), |p| p.start() - 1);
+        let free = limit.0 - part.start().0 + 1;
+        let blocks = length.blocks(self.gpt.block_size(), free);
+        if blocks == 0 || blocks > free {
+            bail!("Not enough free space, at most {}", self.size(free));
+        }
+        self.gpt
+            .resize_partition(index, part.start() + (blocks - 1))?;
+        self.changes.push(format!(
+            "Resize partition {} from {} to {}",
+            number(&part),
+            human(self.gpt.partition_size(&part)),
+            self.size(blocks)
+        ));
+        Ok(())
+    }
+
+    /// Change the type of the selected partition.
+    pub fn set_type(&mut self, p_type: PartitionType) -> Result<()> {
+        let index = self.partition()?;
+        self.gpt
+            .modify_partition(index, |p| p.set_partition_type(p_type))?;
+        self.changes.push(format!(
+            "Set partition {} type to {}",
+            self.number(index),
+            p_type
+        ));
+        Ok(())
+    }
+
+    /// Rename the selected partition.
+    pub fn set_name(&mut self, name: &str) -> Result<()> {
+        let index = self.partition()?;
+        self.gpt.modify_partition(index, |p| p.set_name(name))?;
+        self.changes.push(format!(
+            "Set partition {} name to {:?}",
+            self.number(index),
+            name
+        ));
+        Ok(())
+    }
+}
+
+// Private APIs
+impl Editor {
+    /// Index of the selected partition.
+    fn partition(&self) -> Result<usize> {
+        match self.selected_row() {
+            Some(Row::Partition(i)) => Ok(i),
+            _ => Err(anyhow!("Select a partition first")),
+        }
+    }
+
+    fn index_of(&self, uuid: Uuid) -> usize {
+        self.gpt
+            .find_by_uuid(uuid)
+            .and_then(|(slot, _)| self.gpt.index_of_slot(slot))
+            .expect("BUG: Partition was just added")
+    }
+
+    /// Number of the partition at `index`, see [`number`].
+    fn number(&self, index: usize) -> usize {
+        number(&self.gpt.partitions()[index])
+    }
+
+    fn select_partition(&mut self, index: usize) {
+        if let Some(row) = self.rows().iter().position(|r| *r == Row::Partition(index)) {
+            self.selected = row;
+        }
+    }
+
+    /// `start` rounded up to 1MiB.
+    fn align(&self, start: Block) -> Block {
+        let align = (Size::from_mib(1) / self.gpt.block_size()).0.max(1);
+        Block(start.0.div_ceil(align) * align)
+    }
+
+    fn push_free(&self, rows: &mut Vec<Row>, start: Block, end: Block) {
+        if self.align(start) <= end {
+            rows.push(Row::Free { start, end });
+        }
+    }
+
+    /// Human readable size of `blocks`.
+    fn size(&self, blocks: u64) -> String {
+        human(Size::from_bytes(blocks * self.gpt.block_size().get()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor() -> Editor {
+        let gpt: Gpt = Gpt::new(Uuid::nil(), Size::from_mib(10), BlockSize::new(512));
+        Editor::new(gpt)
+    }
+
+    #[test]
+    fn create_delete() -> Result<()> {
+        let mut editor = editor();
+        assert_eq!(editor.rows().len(), 1);
+        editor.create(
+            Length::Bytes(Size::from_mib(1)),
+            PartitionType::EfiSystem,
+            "EFI",
+        )?;
+        assert_eq!(editor.selected_row(), Some(Row::Partition(0)));
+        assert_eq!(editor.gpt().partitions()[0].start(), Block(2048));
+        assert!(editor.is_modified());
+        // Creating needs free space selected
+        assert!(editor
+            .create(Length::Percent(100), PartitionType::LinuxSwap, "")
+            .is_err());
+        editor.down();
+        editor.create(Length::Percent(100), PartitionType::LinuxSwap, "swap")?;
+        assert_eq!(
+            editor.gpt().partitions()[1].end(),
+            editor.gpt().last_usable()
+        );
+        assert_eq!(editor.rows(), [Row::Partition(0), Row::Partition(1)]);
+        //
+        editor.up();
+        editor.delete()?;
+        assert_eq!(editor.gpt().partitions().len(), 1);
+        assert_eq!(editor.changes().len(), 3);
+        editor.saved();
+        assert!(!editor.is_modified());
+        assert!(editor.changes().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn modify() -> Result<()> {
+        let mut editor = editor();
+        editor.create(
+            Length::Bytes(Size::from_mib(1)),
+            PartitionType::LinuxFilesystemData,
+            "",
+        )?;
+        editor.resize(Length::Bytes(Size::from_mib(2)))?;
+        assert_eq!(editor.gpt().partitions()[0].end(), Block(2048 + 4095));
+        assert!(editor.resize(Length::Bytes(Size::from_mib(20))).is_err());
+        editor.set_type(PartitionType::LinuxLvm)?;
+        editor.set_name("pv0")?;
+        let part = editor.gpt().partitions()[0];
+        assert_eq!(part.partition_type(), PartitionType::LinuxLvm);
+        assert_eq!(part.name(), "pv0");
+        assert!(editor.set_type(PartitionType::Unused).is_err());
+        assert_eq!(editor.changes().len(), 4);
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+//! The `list` command
+use crate::device::Device;
+use anyhow::Result;
+use parts::{sfdisk::Json, types::*, Gpt, PartitionType};
+use std::io::{self, prelude::*};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct List {
+    #[structopt(flatten)]
+    device: Device,
+
+    /// Print `sfdisk --json` compatible JSON
+    #[structopt(long)]
+    json: bool,
+}
+
+impl List {
+    pub fn run(&self) -> Result<()> {
+        let gpt = self.device.read()?;
+        let name = self.device.name();
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        if self.json {
+            serde_json::to_writer_pretty(&mut out, &Json::new(&gpt, &name))?;
+            writeln!(out)?;
+        } else {
+            table(&gpt, &name, &mut out)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write an fdisk style summary of `gpt` to `out`.
+pub fn table<W: Write>(gpt: &Gpt, device: &str, mut out: W) -> io::Result<()> {
+    let block_size = gpt.block_size();
+    let disk_size = gpt.disk_size();
+    writeln!(
+        out,
+        "Disk {}: {}, {} bytes, {} sectors",
+        device,
+        human(disk_size),
+        disk_size.as_bytes(),
+        (disk_size / block_size).0
+    )?;
+    writeln!(out, "Sector size: {} bytes", block_size.get())?;
+    writeln!(out, "Disklabel type: gpt")?;
+    writeln!(out, "Disk identifier: {}", upper(gpt.uuid()))?;
+    if gpt.partitions().is_empty() {
+        return Ok(());
+    }
+    writeln!(out)?;
+    //
+    let mut rows =
+        vec![["#", "Start", "End", "Size", "Type", "Type GUID", "Name"].map(String::from)];
+    for part in gpt.partitions() {
+        let kind = match part.partition_type() {
+            PartitionType::Unknown(_) => "Unknown".into(),
+            kind => kind.to_string(),
+        };
+        rows.push([
+            part.slot().map(|s| (s + 1).to_string()).unwrap_or_default(),
+            part.start().0.to_string(),
+            part.end().0.to_string(),
+            human(gpt.partition_size(part)),
+            kind,
+            upper(part.partition_type().to_uuid()),
+            part.name().into(),
+        ]);
+    }
+    let mut widths = [0; 7];
+    for row in &rows {
+        for (width, col) in widths.iter_mut().zip(row) {
+            *width = (*width).max(col.chars().count());
+        }
+    }
+    for row in &rows {
+        let cols: Vec<_> = row
+            .iter()
+            .zip(&widths)
+            .map(|(col, width)| format!("{:<1$}", col, width))
+            .collect();
+        writeln!(out, "{}", cols.join("  ").trim_end())?;
+    }
+    Ok(())
+}
+
+/// `size` in the largest binary unit it has at least one of,
+/// to one decimal place if not exact.
+pub fn human(size: Size) -> String {
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+    let bytes = size.as_bytes();
+    let mut unit = 0;
+    while unit + 1 < UNITS.len() && bytes >> (10 * (unit + 1)) != 0 {
+        unit += 1;
+    }
+    let scale = 1u64 << (10 * unit);
+    if bytes.is_multiple_of(scale) {
+        format!("{} {}", bytes / scale, UNITS[unit])
+    } else {
+        format!("{:.1} {}", bytes as f64 / scale as f64, UNITS[unit])
+    }
+}
+
+/// Uppercase hyphenated `uuid`, like fdisk.
+pub fn upper(uuid: parts::uuid::Uuid) -> String {
+    uuid.to_hyphenated().to_string().to_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parts::{uuid::Uuid, PartitionBuilder};
+
+    #[test]
+    fn human_sizes() {
+        assert_eq!(human(Size::from_bytes(512)), "512 B");
+        assert_eq!(human(Size::from_mib(1)), "1 MiB");
+        assert_eq!(human(Size::from_mib(1536)), "1.5 GiB");
+        assert_eq!(human(Size::from_bytes(0)), "0 B");
+    }
+
+    #[test]
+    fn list_table() -> Result<()> {
+        let block_size = BlockSize::new(512);
+        let mut gpt: Gpt = Gpt::new(Uuid::from_u128(1), Size::from_mib(10), block_size);
+        let part = PartitionBuilder::new(Uuid::from_u128(2), &gpt)
+            .start(Block(2048))
+            .size(Size::from_mib(1))
+            .partition_type(PartitionType::LinuxFilesystemData)
+            .name("Root")
+            .slot(2)
+            .finish();
+        gpt.add_partition(part)?;
+        let mut out = Vec::new();
+        table(&gpt, "disk.img", &mut out)?;
+        let expected = "\
+Disk disk.img: 10 MiB, 10485760 bytes, 20480 sectors
+Sector size: 512 bytes
+Disklabel type: gpt
+Disk identifier: 00000000-0000-0000-0000-000000000001
+
+#  Start  End   Size   Type                 Type GUID                             Name
+3  2048   4095  1 MiB  LinuxFilesystemData  0FC63DAF-8483-4772-8E79-3D69D8477DE4  Root
+";
+        assert_eq!(String::from_utf8(out)?, expected);
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+//! `parts` command line tool
+//!
+//! Inspect and manage GPT partition tables on devices and disk images.
+use anyhow::Result;
+use structopt::StructOpt;
+
+mod args;
+mod batch;
+mod device;
+mod edit;
+mod interactive;
+mod list;
+
+/// A GPT Partition Manager
+#[derive(Debug, StructOpt)]
+#[structopt(name = "parts")]
+struct Args {
+    #[structopt(subcommand)]
+    cmd: Command,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// List the partitions on a device or disk image
+    List(list::List),
+
+    /// Create a new, empty, GPT on a device or disk image
+    New(edit::New),
+
+    /// Add a partition
+    Add(edit::Edit<edit::Add>),
+
+    /// Delete a partition
+    Delete(edit::Edit<edit::Delete>),
+
+    /// Resize a partition, keeping its start
+    Resize(edit::Edit<edit::Resize>),
+
+    /// Set the name of a partition
+    SetName(edit::Edit<edit::SetName>),
+
+    /// Set the type of a partition
+    SetType(edit::Edit<edit::SetType>),
+
+    /// Set, or clear, attribute bits of a partition
+    SetAttr(edit::Edit<edit::SetAttr>),
+
+    /// Run a script of edits, writing only if every edit succeeds
+    Batch(batch::Batch),
+
+    /// Edit partitions with a full screen interface, like cfdisk
+    Interactive(interactive::Interactive),
+}
+
+fn main() -> Result<()> {
+    let args = Args::from_args();
+    match args.cmd {
+        Command::List(cmd) => cmd.run(),
+        Command::New(cmd) => cmd.run(),
+        Command::Add(cmd) => cmd.run(),
+        Command::Delete(cmd) => cmd.run(),
+        Command::Resize(cmd) => cmd.run(),
+        Command::SetName(cmd) => cmd.run(),
+        Command::SetType(cmd) => cmd.run(),
+        Command::SetAttr(cmd) => cmd.run(),
+        Command::Batch(cmd) => cmd.run(),
+        Command::Interactive(cmd) => cmd.run(),
+    }
+}
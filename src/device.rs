@@ -0,0 +1,487 @@
+//! Block devices
+//!
+//! [`BlockDevice`] is anything a [`GptC`] can be read from or written to,
+//! a whole number of blocks at a time. See [`GptC::from_device`] and
+//! [`GptC::to_device`].
+//!
+//! Implementations are provided for byte slices, [`SliceDevice`] and
+//! [`SliceDeviceMut`], an in-memory [`RamDisk`] with `alloc`,
+//! and with `std` anything [`Read`] + [`Seek`], [`IoDevice`],
+//! or [`Read`] + [`Write`] + [`Seek`], [`IoDeviceMut`].
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use parts::{device::*, Gpt, uuid::Uuid, types::*};
+//! # #[cfg(not(feature = "alloc"))]
+//! # fn main() {}
+//! # #[cfg(feature = "alloc")]
+//! # fn main() {
+//! let mut disk = RamDisk::new(BlockSize::new(512), 20480);
+//! let gpt: Gpt = Gpt::new(Uuid::new_v4(), disk.size(), disk.block_size());
+//! gpt.to_device(&mut disk).unwrap();
+//! assert_eq!(Gpt::from_device(&mut disk).unwrap(), gpt);
+//! # }
+//! ```
+//!
+//! [`GptC`]: crate::GptC
+//! [`GptC::from_device`]: crate::GptC::from_device
+//! [`GptC::to_device`]: crate::GptC::to_device
+//! [`Read`]: std::io::Read
+//! [`Write`]: std::io::Write
+//! [`Seek`]: std::io::Seek
+use crate::{
+    gpt::{check_block_size, error::*},
+    types::*,
+};
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+use core::convert::TryInto;
+#[cfg(feature = "std")]
+use std::io::{prelude::*, SeekFrom};
+
+/// A device made of fixed size blocks.
+///
+/// Every read and write is of whole blocks, and `buf` is always a multiple
+/// of [`BlockDevice::block_size`] bytes. Buffers passed by this crate are
+/// aligned to 4096 bytes, so implementations may use direct I/O.
+///
+/// Block sizes larger than 4096 bytes are not supported.
+pub trait BlockDevice {
+    /// Size of each block.
+    fn block_size(&self) -> BlockSize;
+
+    /// Number of blocks on the device.
+    fn blocks(&self) -> u64;
+
+    /// Read blocks starting at `lba` into `buf`.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::NotEnough`] if this would read past the end of the device.
+    /// - If the device does.
+    fn read_blocks(&mut self, lba: Block, buf: &mut [u8]) -> Result<()>;
+
+    /// Write `buf` to blocks starting at `lba`.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::NotEnough`] if this would write past the end of the device.
+    /// - [`Error::ReadOnly`] if the device can't be written to.
+    /// - If the device does.
+    fn write_blocks(&mut self, lba: Block, buf: &[u8]) -> Result<()>;
+
+    /// Wait for all previous writes to reach the device.
+    ///
+    /// Does nothing by default.
+    ///
+    /// # Errors
+    ///
+    /// - If the device does.
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Size of the device, in bytes.
+    fn size(&self) -> Size {
+        Size::from_bytes(self.blocks() * self.block_size().get())
+    }
+}
+
+impl<D: BlockDevice + ?Sized> BlockDevice for &mut D {
+    fn block_size(&self) -> BlockSize {
+        (**self).block_size()
+    }
+
+    fn blocks(&self) -> u64 {
+        (**self).blocks()
+    }
+
+    fn read_blocks(&mut self, lba: Block, buf: &mut [u8]) -> Result<()> {
+        (**self).read_blocks(lba, buf)
+    }
+
+    fn write_blocks(&mut self, lba: Block, buf: &[u8]) -> Result<()> {
+        (**self).write_blocks(lba, buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
+}
+
+/// Read `buf` at byte `offset` on `device`, which must be whole blocks.
+pub(crate) fn read_at<D: BlockDevice + ?Sized>(
+    device: &mut D,
+    offset: Offset,
+    buf: &mut [u8],
+) -> Result<()> {
+    device.read_blocks(offset / device.block_size(), buf)
+}
+
+/// Write `buf` at byte `offset` on `device`, which must be whole blocks.
+pub(crate) fn write_at<D: BlockDevice + ?Sized>(
+    device: &mut D,
+    offset: Offset,
+    buf: &[u8],
+) -> Result<()> {
+    device.write_blocks(offset / device.block_size(), buf)
+}
+
+/// Check `device` is usable for a Gpt.
+pub(crate) fn check<D: BlockDevice + ?Sized>(device: &D) -> Result<()> {
+    check_block_size(device.block_size())
+}
+
+/// The byte range of `len` bytes at `lba`, if it's within `blocks`.
+fn range(lba: Block, len: usize, block_size: BlockSize, blocks: u64) -> Result<(u64, u64)> {
+    if lba.0 > blocks {
+        return Err(Error::NotEnough);
+    }
+    let start = lba.0 * block_size.get();
+    let end = start + len as u64;
+    if end > blocks * block_size.get() {
+        return Err(Error::NotEnough);
+    }
+    Ok((start, end))
+}
+
+/// [`range`] as indices into a slice.
+fn slice_range(
+    lba: Block,
+    len: usize,
+    block_size: BlockSize,
+    blocks: u64,
+) -> Result<(usize, usize)> {
+    let (start, end) = range(lba, len, block_size, blocks)?;
+    let start = start.try_into().map_err(|_| Error::NotEnough)?;
+    let end = end.try_into().map_err(|_| Error::NotEnough)?;
+    Ok((start, end))
+}
+
+/// A read only device in a byte slice.
+///
+/// Any partial block at the end of the slice is ignored.
+#[derive(Debug)]
+pub struct SliceDevice<'a> {
+    data: &'a [u8],
+    block_size: BlockSize,
+}
+
+impl<'a> SliceDevice<'a> {
+    /// Use `data` as a device with blocks of `block_size`.
+    pub fn new(data: &'a [u8], block_size: BlockSize) -> Self {
+        Self { data, block_size }
+    }
+}
+
+impl BlockDevice for SliceDevice<'_> {
+    fn block_size(&self) -> BlockSize {
+        self.block_size
+    }
+
+    fn blocks(&self) -> u64 {
+        self.data.len() as u64 / self.block_size.get()
+    }
+
+    fn read_blocks(&mut self, lba: Block, buf: &mut [u8]) -> Result<()> {
+        let (start, end) = slice_range(lba, buf.len(), self.block_size, self.blocks())?;
+        buf.copy_from_slice(&self.data[start..end]);
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, _: Block, _: &[u8]) -> Result<()> {
+        Err(Error::ReadOnly)
+    }
+}
+
+/// A device in a mutable byte slice.
+///
+/// Any partial block at the end of the slice is ignored.
+#[derive(Debug)]
+pub struct SliceDeviceMut<'a> {
+    data: &'a mut [u8],
+    block_size: BlockSize,
+}
+
+impl<'a> SliceDeviceMut<'a> {
+    /// Use `data` as a device with blocks of `block_size`.
+    pub fn new(data: &'a mut [u8], block_size: BlockSize) -> Self {
+        Self { data, block_size }
+    }
+}
+
+impl BlockDevice for SliceDeviceMut<'_> {
+    fn block_size(&self) -> BlockSize {
+        self.block_size
+    }
+
+    fn blocks(&self) -> u64 {
+        self.data.len() as u64 / self.block_size.get()
+    }
+
+    fn read_blocks(&mut self, lba: Block, buf: &mut [u8]) -> Result<()> {
+        let (start, end) = slice_range(lba, buf.len(), self.block_size, self.blocks())?;
+        buf.copy_from_slice(&self.data[start..end]);
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, lba: Block, buf: &[u8]) -> Result<()> {
+        let (start, end) = slice_range(lba, buf.len(), self.block_size, self.blocks())?;
+        self.data[start..end].copy_from_slice(buf);
+        Ok(())
+    }
+}
+
+/// An in-memory device, initially all zeros.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RamDisk {
+    data: Vec<u8>,
+    block_size: BlockSize,
+}
+
+#[cfg(feature = "alloc")]
+impl RamDisk {
+    /// New zeroed disk of `blocks` blocks of `block_size`.
+    ///
+    /// # Panics
+    ///
+    /// - If the disk doesn't fit in memory.
+    pub fn new(block_size: BlockSize, blocks: u64) -> Self {
+        let len = blocks
+            .checked_mul(block_size.get())
+            .expect("RamDisk too large");
+        Self {
+            data: vec![0; len as usize],
+            block_size,
+        }
+    }
+
+    /// The contents of the disk.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The contents of the disk.
+    pub fn into_inner(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl BlockDevice for RamDisk {
+    fn block_size(&self) -> BlockSize {
+        self.block_size
+    }
+
+    fn blocks(&self) -> u64 {
+        self.data.len() as u64 / self.block_size.get()
+    }
+
+    fn read_blocks(&mut self, lba: Block, buf: &mut [u8]) -> Result<()> {
+        SliceDeviceMut::new(&mut self.data, self.block_size).read_blocks(lba, buf)
+    }
+
+    fn write_blocks(&mut self, lba: Block, buf: &[u8]) -> Result<()> {
+        SliceDeviceMut::new(&mut self.data, self.block_size).write_blocks(lba, buf)
+    }
+}
+
+/// A read only device using [`Read`] and [`Seek`].
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct IoDevice<T> {
+    inner: T,
+    block_size: BlockSize,
+    blocks: u64,
+}
+
+#[cfg(feature = "std")]
+impl<T: Seek> IoDevice<T> {
+    /// Use `inner` as a device with blocks of `block_size`.
+    ///
+    /// The number of blocks is found by seeking to the end of `inner`.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Io`] if seeking does.
+    pub fn new(mut inner: T, block_size: BlockSize) -> Result<Self> {
+        let size = inner.seek(SeekFrom::End(0))?;
+        Ok(Self {
+            inner,
+            block_size,
+            blocks: size / block_size.get(),
+        })
+    }
+
+    /// Return the underlying device
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Seek to `lba`, checking `len` bytes fit.
+    fn seek_to(&mut self, lba: Block, len: usize) -> Result<()> {
+        let (start, _) = range(lba, len, self.block_size, self.blocks)?;
+        self.inner.seek(SeekFrom::Start(start))?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Read + Seek> BlockDevice for IoDevice<T> {
+    fn block_size(&self) -> BlockSize {
+        self.block_size
+    }
+
+    fn blocks(&self) -> u64 {
+        self.blocks
+    }
+
+    fn read_blocks(&mut self, lba: Block, buf: &mut [u8]) -> Result<()> {
+        self.seek_to(lba, buf.len())?;
+        self.inner.read_exact(buf)?;
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, _: Block, _: &[u8]) -> Result<()> {
+        Err(Error::ReadOnly)
+    }
+}
+
+/// A device using [`Read`], [`Write`], and [`Seek`], such as a [`File`].
+///
+/// [`BlockDevice::flush`] calls [`Write::flush`], which for a [`File`]
+/// does not wait for the disk.
+///
+/// [`File`]: std::fs::File
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct IoDeviceMut<T>(IoDevice<T>);
+
+#[cfg(feature = "std")]
+impl<T: Seek> IoDeviceMut<T> {
+    /// Use `inner` as a device with blocks of `block_size`.
+    ///
+    /// The number of blocks is found by seeking to the end of `inner`.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Io`] if seeking does.
+    pub fn new(inner: T, block_size: BlockSize) -> Result<Self> {
+        Ok(Self(IoDevice::new(inner, block_size)?))
+    }
+
+    /// Return the underlying device
+    pub fn into_inner(self) -> T {
+        self.0.into_inner()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Read + Write + Seek> BlockDevice for IoDeviceMut<T> {
+    fn block_size(&self) -> BlockSize {
+        self.0.block_size
+    }
+
+    fn blocks(&self) -> u64 {
+        self.0.blocks
+    }
+
+    fn read_blocks(&mut self, lba: Block, buf: &mut [u8]) -> Result<()> {
+        self.0.read_blocks(lba, buf)
+    }
+
+    fn write_blocks(&mut self, lba: Block, buf: &[u8]) -> Result<()> {
+        self.0.seek_to(lba, buf.len())?;
+        self.0.inner.write_all(buf)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.0.inner.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slices() -> Result<()> {
+        let mut data = [0; 512 * 4 + 100];
+        let mut dev = SliceDeviceMut::new(&mut data, BlockSize::new(512));
+        assert_eq!(dev.blocks(), 4);
+        assert_eq!(dev.size(), Size::from_bytes(2048));
+        dev.write_blocks(Block(2), &[1; 1024])?;
+        assert!(matches!(
+            dev.write_blocks(Block(3), &[1; 1024]),
+            Err(Error::NotEnough)
+        ));
+        let mut buf = [0; 512];
+        let mut dev = SliceDevice::new(&data, BlockSize::new(512));
+        dev.read_blocks(Block(3), &mut buf)?;
+        assert_eq!(buf, [1; 512]);
+        assert!(matches!(
+            dev.read_blocks(Block(4), &mut buf),
+            Err(Error::NotEnough)
+        ));
+        assert!(matches!(
+            dev.write_blocks(Block(0), &buf),
+            Err(Error::ReadOnly)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn gpt() -> crate::util::Result {
+        use crate::{util::*, Gpt, PartitionBuilder, PartitionType};
+        use std::io::Cursor;
+        use uuid::Uuid;
+
+        for &block_size in &[BLOCK_SIZE, LARGE_BLOCK_SIZE] {
+            let mut disk = RamDisk::new(block_size, TEN_MIB_BYTES as u64 / block_size.get());
+            let mut gpt: Gpt = Gpt::new(Uuid::from_u128(1), disk.size(), block_size);
+            let part = PartitionBuilder::new(Uuid::from_u128(2), &gpt)
+                .start(Size::from_mib(1) / block_size)
+                .size(Size::from_mib(1))
+                .partition_type(PartitionType::LinuxFilesystemData)
+                .finish();
+            gpt.add_partition(part)?;
+            gpt.to_device(&mut disk)?;
+            assert_eq!(Gpt::from_device(&mut disk)?, gpt);
+
+            // Same bytes as every other way of writing it
+            let mut bytes = vec![0; TEN_MIB_BYTES];
+            gpt.to_bytes(&mut bytes)?;
+            assert!(disk.as_bytes() == &bytes[..]);
+            assert_eq!(
+                <Gpt>::from_device(SliceDevice::new(&bytes, block_size))?,
+                gpt
+            );
+            assert_eq!(
+                <Gpt>::from_device(IoDevice::new(Cursor::new(&bytes), block_size)?)?,
+                gpt
+            );
+
+            // Incremental writes through `Write + Seek`
+            gpt.remove_partition(0);
+            let mut io = IoDeviceMut::new(Cursor::new(bytes), block_size)?;
+            gpt.to_device_incremental(&mut io)?;
+            gpt.to_device(SliceDeviceMut::new(&mut disk.data, block_size))?;
+            assert!(io.into_inner().into_inner() == disk.into_inner());
+
+            // Read only devices
+            let bytes = vec![0; TEN_MIB_BYTES];
+            let dev = SliceDevice::new(&bytes, block_size);
+            assert!(matches!(gpt.to_device(dev), Err(Error::ReadOnly)));
+        }
+        // Block size mismatch
+        let gpt: Gpt = Gpt::new(Uuid::nil(), Size::from_mib(10), BLOCK_SIZE);
+        let disk = RamDisk::new(LARGE_BLOCK_SIZE, 2560);
+        assert!(matches!(gpt.to_device(disk), Err(Error::Invalid(_))));
+        Ok(())
+    }
+}
@@ -1,80 +1,249 @@
 //! Raw Gpt stuff
 use self::{error::*, header::*, partition::*};
+#[cfg(feature = "alloc")]
+use crate::write::PlannedWrite;
 use crate::{
+    device::{self, BlockDevice},
     mbr::{ProtectiveMbr, MBR_SIZE},
+    partitions::PartitionType,
     types::*,
+    write::Region,
 };
 #[cfg(feature = "alloc")]
 use alloc::vec::Vec;
 use arrayvec::{Array, ArrayVec};
 use core::convert::TryInto;
+#[cfg(feature = "serde")]
+use core::{fmt, marker::PhantomData};
 use crc::{crc32, Hasher32};
+#[cfg(feature = "serde")]
+use serde_crate::{
+    de::{self, SeqAccess, Visitor},
+    ser::SerializeStruct,
+    Deserialize,
+    Deserializer,
+    Serialize,
+    Serializer,
+};
 #[cfg(feature = "std")]
 use std::io::{prelude::*, SeekFrom};
 use uuid::Uuid;
 
 pub mod error;
-mod header;
+pub(crate) mod header;
 pub mod partition;
 
-/// Validate the Gpt Headers.
+/// Validate one copy of the Gpt, `header` and its partition array.
 ///
-/// Calls `cb` when going through the primary partition array.
+/// Calls `cb` when going through the partition array.
 /// This is used to add partitions in `GptC`
 ///
 /// Per the GPT Spec this checks:
 ///
 /// - The signature (Checked in [`Header::from_bytes`])
 /// - Header CRC (Checked in [`Header::from_bytes`])
-/// - [`Header::this`]
+/// - [`Header::this`], which must be `this`
+/// - [`Header::alt`], which must be `alt`
 /// - Partition CRC
-/// - For the primary, [`Header::alt`]
 fn validate<F: FnMut(Offset, &mut [u8]) -> Result<()>, CB: FnMut(usize, &[u8]) -> Result<()>>(
-    primary: &Header,
-    alt: &Header,
+    header: &Header,
+    kind: HeaderKind,
     mut func: F,
     block_size: BlockSize,
-    disk_size: Size,
+    (this, alt): (Block, Block),
     mut cb: CB,
 ) -> Result<()> {
-    if primary.this != Block(1) {
-        return Err(Error::Invalid("Primary header location invalid"));
+    let (location, alternate, array) = match kind {
+        HeaderKind::Primary => (
+            "Primary header location invalid",
+            "Primary header alternate location invalid",
+            "Primary Partition Array CRC32 mismatch",
+        ),
+        HeaderKind::Backup => (
+            "Corrupt Backup GPT Header",
+            "Corrupt Backup GPT Header",
+            "Backup Partition Array CRC32 mismatch",
+        ),
+    };
+    if header.this != this {
+        return Err(Error::Invalid(location));
+    }
+    if header.alt != alt {
+        return Err(Error::Invalid(alternate));
+    }
+    if header.entry_size < PARTITION_ENTRY_SIZE {
+        return Err(Error::Invalid("Partition entry size invalid"));
     }
     let crc = calculate_part_crc(
         &mut func,
-        primary.partitions as u64,
-        primary.array * block_size,
-        primary.entry_size as usize,
+        header.partitions as u64,
+        header.array * block_size,
+        header.entry_size as usize,
+        block_size,
         &mut cb,
     )?;
-    if crc != primary.partitions_crc32 {
-        return Err(Error::Invalid("Primary Partition Array CRC32 mismatch"));
+    if crc != header.partitions_crc32 {
+        return Err(Error::Invalid(array));
     }
-    let last_lba = (disk_size / block_size) - 1;
-    if primary.alt != last_lba {
-        return Err(Error::Invalid("Primary header alternate location invalid"));
+    Ok(())
+}
+
+/// Check that a disk of `disk_size` can hold a GPT.
+///
+/// It must fit the MBR, both headers and arrays, and at least one usable
+/// block.
+///
+/// # Errors
+///
+/// - [`Error::Invalid`] if it can't.
+pub(crate) fn check_disk_size(block_size: BlockSize, disk_size: Size) -> Result<()> {
+    let array = (MIN_PARTITIONS_BYTES / block_size).0;
+    if (disk_size / block_size).0 < (array * 2) + 4 {
+        return Err(Error::Invalid("Disk too small"));
     }
-    //
-    if alt.this != last_lba {
-        return Err(Error::Invalid("Corrupt Backup GPT Header"));
+    Ok(())
+}
+
+/// Whether `e` means a copy of the Gpt is damaged, rather than unreadable.
+fn damaged(e: &Error) -> bool {
+    matches!(e, Error::Invalid(_) | Error::NotEnough)
+}
+
+/// Largest single read or write, in bytes.
+///
+/// All reads and writes are of whole blocks, starting on a block boundary,
+/// in chunks of at most this many bytes. This keeps buffers on the stack
+/// small, and works with devices that need aligned I/O.
+///
+/// Blocks larger than this are read and written in pieces of this size.
+pub(crate) const CHUNK_SIZE: usize = 4096;
+
+/// A [`CHUNK_SIZE`] buffer, aligned to [`CHUNK_SIZE`].
+///
+/// Every buffer passed to a read or write function is part of one of these,
+/// starting on a block boundary, so they're aligned for direct I/O.
+#[repr(C, align(4096))]
+pub(crate) struct ChunkBuf(pub(crate) [u8; CHUNK_SIZE]);
+
+impl ChunkBuf {
+    pub(crate) fn new() -> Self {
+        Self([0; CHUNK_SIZE])
     }
-    if alt.alt != Block(1) {
-        return Err(Error::Invalid("Corrupt Backup GPT Header"));
+}
+
+/// Check `block_size` fits in a [`ChunkBuf`].
+///
+/// # Errors
+///
+/// - [`Error::Invalid`] if it's larger than [`CHUNK_SIZE`].
+pub(crate) fn check_block_size(block_size: BlockSize) -> Result<()> {
+    if block_size.get() > CHUNK_SIZE as u64 {
+        return Err(Error::Invalid("Block size larger than 4096 bytes"));
     }
-    let crc = calculate_part_crc(
-        &mut func,
-        alt.partitions as u64,
-        alt.array * block_size,
-        alt.entry_size as usize,
-        &mut |_, _| Ok(()),
-    )?;
-    if crc != alt.partitions_crc32 {
-        return Err(Error::Invalid("Backup Partition Array CRC32 mismatch"));
+    Ok(())
+}
+
+/// Length of each chunk, a whole number of blocks.
+fn chunk_len(block_size: BlockSize) -> usize {
+    CHUNK_SIZE - (CHUNK_SIZE % block_size.get() as usize)
+}
+
+/// `len` rounded up to whole blocks.
+fn whole_blocks(len: u64, block_size: BlockSize) -> u64 {
+    len.div_ceil(block_size.get()) * block_size.get()
+}
+
+/// Read block `lba` into `buf` using `func`.
+fn read_block<'a, F: FnMut(Offset, &mut [u8]) -> Result<()>>(
+    func: &mut F,
+    lba: Block,
+    block_size: BlockSize,
+    buf: &'a mut ChunkBuf,
+) -> Result<&'a [u8]> {
+    let buf = &mut buf.0[..block_size.get() as usize];
+    func(lba * block_size, buf)?;
+    Ok(buf)
+}
+
+/// Read `len` bytes at `offset` using `func`, in chunks, passing each to
+/// `cb`.
+///
+/// `offset` and `len` must be whole blocks.
+pub(crate) fn read_chunks<F, CB>(
+    func: &mut F,
+    offset: Offset,
+    len: u64,
+    block_size: BlockSize,
+    mut cb: CB,
+) -> Result<()>
+where
+    F: FnMut(Offset, &mut [u8]) -> Result<()>,
+    CB: FnMut(&[u8]) -> Result<()>,
+{
+    let mut buf = ChunkBuf::new();
+    let chunk = chunk_len(block_size) as u64;
+    let mut pos = 0;
+    while pos < len {
+        let buf = &mut buf.0[..chunk.min(len - pos) as usize];
+        func(Offset(offset.0 + pos), buf)?;
+        cb(buf)?;
+        pos += buf.len() as u64;
+    }
+    Ok(())
+}
+
+/// Write `len` bytes of `region` at `offset` using `func`, in chunks.
+///
+/// `fill` receives each zeroed chunk to fill,
+/// and its position relative to `offset`.
+///
+/// `offset` and `len` must be whole blocks.
+fn write_chunks<F, FI>(
+    func: &mut F,
+    region: Region,
+    offset: Offset,
+    len: u64,
+    block_size: BlockSize,
+    mut fill: FI,
+) -> Result<()>
+where
+    F: FnMut(Region, Offset, &[u8]) -> Result<()>,
+    FI: FnMut(u64, &mut [u8]) -> Result<()>,
+{
+    let mut buf = ChunkBuf::new();
+    let chunk = chunk_len(block_size) as u64;
+    let mut pos = 0;
+    while pos < len {
+        let buf = &mut buf.0[..chunk.min(len - pos) as usize];
+        buf.iter_mut().for_each(|b| *b = 0);
+        fill(pos, buf)?;
+        func(region, Offset(offset.0 + pos), buf)?;
+        pos += buf.len() as u64;
     }
-    //
     Ok(())
 }
 
+/// Slot of `part`, which every partition in a [`GptC`] has.
+pub(crate) fn slot(part: &Partition) -> usize {
+    part.slot().expect("Partition without a slot")
+}
+
+/// Which copy of a [`GptC`] was read, and whether the other is damaged.
+///
+/// See [`GptC::from_bytes_with_status`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Status {
+    /// Both copies are valid and match. The primary was used.
+    Valid,
+
+    /// The primary was used, but the backup is damaged or doesn't match it.
+    BackupDamaged,
+
+    /// The primary is damaged, so the backup was used.
+    PrimaryDamaged,
+}
+
 /// Helper trait for [`GptC`].
 ///
 /// You shouldn't need to worry about this.
@@ -224,6 +393,11 @@ impl<C: GptHelper<C>> Gpt<C> {
 
     /// Read the Gpt from `source`
     ///
+    /// If the primary header or partition array is damaged, such as by an
+    /// interrupted write, the backup is used instead.
+    /// Writing the Gpt back out will repair it.
+    /// Use [`GptC::from_bytes_with_status`] to find out which copy was used.
+    ///
     /// # Errors
     ///
     /// - [`Error::Invalid`] if the GPT is invalid
@@ -249,18 +423,22 @@ impl<C: GptHelper<C>> Gpt<C> {
 
     /// Read the Gpt using `func`.
     ///
-    /// `primary` must contain LBA0 and LBA1, for a length of `block_size * 2`
-    /// bytes.
-    ///
-    /// `alt` must contain the last LBA, for a length of `block_size` bytes.
-    ///
     /// `func` receives a byte offset into the device,
     /// and a buffer to read into.
+    /// Both the offset and the buffer length are multiples of `block_size`,
+    /// and each read is at most 4096 bytes, into a buffer aligned to 4096
+    /// bytes. The partition array is read in as few reads as possible.
+    ///
+    /// Note that the MBR and headers are read as whole blocks too,
+    /// not just the bytes they use, so `func` must be able to read all of
+    /// the first two blocks and the last block of a `disk_size` device.
+    /// Block sizes larger than 4096 bytes aren't supported.
     ///
     /// # Errors
     ///
-    /// - [`Error::Invalid`] if the GPT is invalid
-    /// - [`Error::NotEnough`] if `source`/`primary`/`alt` is too small.
+    /// - [`Error::Invalid`] if the GPT is invalid, or `block_size` is larger
+    ///   than 4096 bytes.
+    /// - [`Error::NotEnough`] if the device is too small.
     /// - If `func` does.
     ///
     /// # Examples
@@ -292,59 +470,68 @@ impl<C: GptHelper<C>> Gpt<C> {
     /// # Ok(()) }
     /// ```
     pub fn from_bytes_with_func<F: FnMut(Offset, &mut [u8]) -> Result<()>>(
-        mut func: F,
+        func: F,
         block_size: BlockSize,
         disk_size: Size,
     ) -> Result<Self> {
-        let _mbr = {
-            let mut buf = [0; MBR_SIZE];
-            func(Offset(0), &mut buf)?;
-            ProtectiveMbr::from_bytes(&buf)?
-        };
-        let primary = {
-            // NOTE: `block_size - 92` is reserved and must be zero, but we don't check.
-            // FIXME: CRC is over header_size, which may be larger.
-            let mut buf = [0; 92];
-            func(Offset(block_size.get()), &mut buf)?;
-            Header::from_bytes(&buf, block_size)?
-        };
-        let alt = {
-            // NOTE: `block_size - 92` is reserved and must be zero, but we don't check.
-            // FIXME: CRC is over header_size, which may be larger.
-            let mut buf = [0; 92];
-            let last: Block = (disk_size / block_size) - 1;
-            func(last * block_size, &mut buf)?;
-            Header::from_bytes(&buf, block_size)?
-        };
-        //
-        let mut partitions = C::new();
-        validate(
-            &primary,
-            &alt,
-            &mut func,
-            block_size,
-            disk_size,
-            |_, source| {
-                let part = Partition::from_bytes(source)?;
-                if part != Partition::new() {
-                    let _ = partitions.push(part);
-                }
-                Ok(())
-            },
-        )?;
+        GptC::read_with_func(func, block_size, disk_size, false).map(|(gpt, _)| gpt)
+    }
 
-        Ok(GptC {
-            uuid: primary.uuid,
-            partitions,
-            disk_size,
-            block_size,
-        })
+    /// Read the Gpt using `func`, and report which copy was used.
+    ///
+    /// See [`GptC::from_bytes_with_func`] for details.
+    ///
+    /// Both copies are always read. The returned [`Status`] says whether the
+    /// primary or backup was used, and whether the other is damaged.
+    /// A copy is damaged if it's invalid or doesn't fit on the disk,
+    /// not if `func` fails to read it.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Invalid`] if neither copy is valid.
+    /// - [`Error::NotEnough`] if the device is too small.
+    /// - If `func` does.
+    ///
+    /// # Examples
+    ///
+    /// Warn about a damaged copy, which writing the Gpt back out will repair.
+    ///
+    /// ```rust
+    /// # use parts::{Gpt, Status, Error, uuid::Uuid, types::*};
+    /// # let block_size = BlockSize::new(512);
+    /// # let mut source = vec![0; Size::from_mib(1).as_bytes() as usize];
+    /// # let gpt: Gpt = Gpt::new(Uuid::nil(), Size::from_mib(1), block_size);
+    /// # gpt.to_bytes(&mut source).unwrap();
+    /// # source[1024] ^= 1;
+    /// let (gpt, status): (Gpt, _) = Gpt::from_bytes_with_status(
+    ///     |offset, buf| {
+    ///         let offset = offset.0 as usize;
+    ///         let data = source.get(offset..offset + buf.len()).ok_or(Error::NotEnough)?;
+    ///         buf.copy_from_slice(data);
+    ///         Ok(())
+    ///     },
+    ///     block_size,
+    ///     Size::from_mib(1),
+    /// )
+    /// .unwrap();
+    /// assert_eq!(status, Status::PrimaryDamaged);
+    /// ```
+    pub fn from_bytes_with_status<F: FnMut(Offset, &mut [u8]) -> Result<()>>(
+        func: F,
+        block_size: BlockSize,
+        disk_size: Size,
+    ) -> Result<(Self, Status)> {
+        GptC::read_with_func(func, block_size, disk_size, false)
     }
 
     /// Read the Gpt from `source`
     ///
     /// See [`GptC::from_bytes`] for more details.
     ///
+    /// All reads are of whole, aligned blocks, into buffers aligned to 4096
+    /// bytes, so `source` may be opened with `O_DIRECT`,
+    /// for block sizes up to 4096.
+    ///
     /// # Errors
     ///
     /// - [`Error::Invalid`] if the GPT is invalid
@@ -377,10 +564,16 @@ impl<C: GptHelper<C>> Gpt<C> {
     /// The Gpt will be written in the following order
     ///
     /// - The Protective MBR
-    /// - The backup header
     /// - The backup partition array
-    /// - The primary header
+    /// - The backup header
     /// - The primary partition array
+    /// - The primary header
+    ///
+    /// Each header is written after its partition array, and the backup
+    /// copy entirely before the primary, so if writing is interrupted at
+    /// most one copy is damaged, and reading will use the other.
+    /// See [`GptC::to_bytes_with_flush`] to make sure each copy reaches the
+    /// disk before the next is started.
     pub fn to_bytes(&self, dest: &mut [u8]) -> Result<()> {
         let size = Size::from_bytes(dest.len().try_into().unwrap());
         self.to_bytes_with_func(
@@ -402,134 +595,681 @@ impl<C: GptHelper<C>> Gpt<C> {
     ///
     /// `func` receives a byte offset, and a buffer to read from.
     /// It is your responsibility to write the buffer to the device.
+    /// Like reads, writes are whole, aligned blocks of at most 4096 bytes.
+    ///
+    /// Note that this means the MBR and headers are written as whole blocks,
+    /// with the rest of each block zeroed, overwriting anything there.
+    /// Block sizes larger than 4096 bytes aren't supported.
     ///
     /// See [`GptC::to_bytes`] for more details.
     ///
     /// # Errors
     ///
+    /// - [`Error::Invalid`] if `block_size` is larger than 4096 bytes.
     /// - If `func` does.
     /// - If all partitions do not fit within the usable blocks.
     ///
     /// # Examples
     pub fn to_bytes_with_func<F: FnMut(Offset, &[u8]) -> Result<()>>(
         &self,
-        mut func: F,
+        func: F,
         block_size: BlockSize,
         disk_size: Size,
     ) -> Result<()> {
-        let last_lba = (disk_size / block_size) - 1;
-        {
-            let mbr = ProtectiveMbr::new(last_lba);
-            let mut mbr_buf = [0; MBR_SIZE];
-            mbr.to_bytes(&mut mbr_buf)?;
-            func(Size::from_bytes(0).into(), &mbr_buf)?;
-        }
-
-        let partition_len = self
-            .partitions
-            .as_slice()
-            .len()
-            .try_into()
-            .expect("Too many partitions, would overflow u32");
-        let mut partition_buf = [0; PARTITION_ENTRY_SIZE as usize];
-        let mut digest = crc32::Digest::new(crc32::IEEE);
-        for part in self.partitions.as_slice() {
-            part.to_bytes(&mut partition_buf)?;
-            digest.write(&partition_buf);
-        }
-        let parts_crc = digest.sum32();
-        let disk_uuid = self.uuid;
+        self.to_bytes_with_flush(func, || Ok(()), block_size, disk_size)
+    }
 
-        let alt = Header::new(
-            HeaderKind::Backup,
-            partition_len,
-            parts_crc,
-            disk_uuid,
+    /// Write the Gpt using `func`, calling `flush` once the backup copy has
+    /// been written, and again once the primary copy has been written.
+    ///
+    /// `flush` should wait for all previous writes to reach the disk,
+    /// such as with [`File::sync_data`], so a crash can never leave both
+    /// copies damaged.
+    ///
+    /// See [`GptC::to_bytes`] for more details.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Invalid`] if `block_size` is larger than 4096 bytes.
+    /// - If `func` or `flush` do.
+    /// - If all partitions do not fit within the usable blocks.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use parts::{Gpt, types::*};
+    /// # #[cfg(feature = "std")]
+    /// # use std::{fs::OpenOptions, io::{prelude::*, SeekFrom}};
+    /// # #[cfg(not(feature = "std"))]
+    /// # fn main() {}
+    /// # #[cfg(feature = "std")]
+    /// # fn main() -> anyhow::Result<()> {
+    /// let mut file = OpenOptions::new().read(true).write(true).open("/dev/sda")?;
+    /// let gpt: Gpt = Gpt::from_reader(&mut file, BlockSize::new(512))?;
+    /// gpt.to_bytes_with_flush(
+    ///     |offset, buf| {
+    ///         // `&File` can be written to, so both closures can share it.
+    ///         let mut file = &file;
+    ///         file.seek(SeekFrom::Start(offset.0))?;
+    ///         file.write_all(buf)?;
+    ///         Ok(())
+    ///     },
+    ///     || Ok(file.sync_data()?),
+    ///     gpt.block_size(),
+    ///     gpt.disk_size(),
+    /// )?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [`File::sync_data`]: https://doc.rust-lang.org/std/fs/struct.File.html#method.sync_data
+    pub fn to_bytes_with_flush<F, FL>(
+        &self,
+        mut func: F,
+        flush: FL,
+        block_size: BlockSize,
+        disk_size: Size,
+    ) -> Result<()>
+    where
+        F: FnMut(Offset, &[u8]) -> Result<()>,
+        FL: FnMut() -> Result<()>,
+    {
+        self.write_regions(
+            |_, offset, buf| func(offset, buf),
+            flush,
             block_size,
             disk_size,
-        );
-        // Verify all partitions are within bounds
-        for part in self.partitions() {
-            let a = part.start();
-            let b = part.end();
-            if (a < alt.first_usable) || (b > alt.last_usable) {
-                return Err(Error::NotEnough);
-            }
-        }
+        )
+    }
 
-        self.write_header_array(&mut func, alt, last_lba, block_size)?;
+    /// Write only what changed, using `read` to compare against what is
+    /// already on the device, and `write` for the rest.
+    ///
+    /// Every write [`GptC::to_bytes_with_flush`] would make is first read
+    /// back with `read`, and skipped if the device already has those bytes,
+    /// so blocks whose content doesn't change are never written.
+    /// The order, and the calls to `flush`, are the same,
+    /// so this is just as safe to interrupt.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Invalid`] if `block_size` is larger than 4096 bytes.
+    /// - If `read`, `write` or `flush` do.
+    /// - If all partitions do not fit within the usable blocks.
+    pub fn to_bytes_incremental_with_func<R, W, FL>(
+        &self,
+        mut read: R,
+        mut write: W,
+        flush: FL,
+        block_size: BlockSize,
+        disk_size: Size,
+    ) -> Result<()>
+    where
+        R: FnMut(Offset, &mut [u8]) -> Result<()>,
+        W: FnMut(Offset, &[u8]) -> Result<()>,
+        FL: FnMut() -> Result<()>,
+    {
+        let block = block_size.get() as usize;
+        let mut old = ChunkBuf::new();
+        self.write_regions(
+            |_, offset, buf| {
+                let old = &mut old.0[..buf.len()];
+                read(offset, old)?;
+                // Write each run of changed blocks
+                let mut changed = None;
+                for start in (0..buf.len()).step_by(block) {
+                    let end = (start + block).min(buf.len());
+                    if old[start..end] != buf[start..end] {
+                        changed.get_or_insert(start);
+                    } else if let Some(from) = changed.take() {
+                        write(Offset(offset.0 + from as u64), &buf[from..start])?;
+                    }
+                }
+                if let Some(from) = changed {
+                    write(Offset(offset.0 + from as u64), &buf[from..])?;
+                }
+                Ok(())
+            },
+            flush,
+            block_size,
+            disk_size,
+        )
+    }
 
-        let primary = Header::new(
-            HeaderKind::Primary,
-            partition_len,
-            parts_crc,
-            disk_uuid,
+    /// The writes [`GptC::to_bytes_with_func`] would make, in order,
+    /// without making them.
+    ///
+    /// Consecutive writes to the same [`Region`] are merged into one.
+    ///
+    /// # Errors
+    ///
+    /// - If all partitions do not fit within the usable blocks.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use parts::{Gpt, uuid::Uuid, types::*, write::Region};
+    /// let gpt: Gpt = Gpt::new(Uuid::nil(), Size::from_mib(10), BlockSize::new(512));
+    /// let plan = gpt.write_plan(gpt.block_size(), gpt.disk_size()).unwrap();
+    /// assert_eq!(plan[0].region, Region::Mbr);
+    /// for write in &plan {
+    ///     println!("{}", write);
+    /// }
+    /// ```
+    ///
+    /// [`Region`]: crate::write::Region
+    #[cfg(feature = "alloc")]
+    pub fn write_plan(&self, block_size: BlockSize, disk_size: Size) -> Result<Vec<PlannedWrite>> {
+        let mut plan: Vec<PlannedWrite> = Vec::new();
+        self.write_regions(
+            |region, offset, buf| {
+                let len = Size::from_bytes(buf.len() as u64);
+                match plan.last_mut() {
+                    Some(last)
+                        if last.region == region
+                            && last.offset.0 + last.len.as_bytes() == offset.0 =>
+                    {
+                        last.len += len;
+                    }
+                    _ => plan.push(PlannedWrite {
+                        region,
+                        offset,
+                        len,
+                    }),
+                }
+                Ok(())
+            },
+            || Ok(()),
             block_size,
             disk_size,
-        );
-        self.write_header_array(func, primary, Block(1), block_size)?;
-        Ok(())
+        )?;
+        Ok(plan)
     }
 
     /// Write the Gpt to `dest`
     ///
+    /// `dest` is flushed after each copy, see [`GptC::to_bytes_with_flush`].
+    /// Note that for a [`File`] this does not wait for the disk.
+    ///
+    /// Like [`GptC::from_reader`], `dest` may be opened with `O_DIRECT`.
+    ///
     /// # Errors
     ///
     /// - If all partitions do not fit within the usable blocks.
     /// - [`Error::NotEnough`] if `dest` is too small.
     /// - [`Error::Io`] if I/O does.
+    ///
+    /// [`File`]: std::fs::File
     #[cfg(feature = "std")]
-    pub fn to_writer<WS: Write + Seek>(&self, mut dest: WS) -> Result<()> {
-        let disk_size = Size::from_bytes(dest.seek(SeekFrom::End(0))?);
-        dest.seek(SeekFrom::Start(0))?;
-        self.to_bytes_with_func(
+    pub fn to_writer<WS: Write + Seek>(&self, dest: WS) -> Result<()> {
+        let dest = core::cell::RefCell::new(dest);
+        let disk_size = Size::from_bytes(dest.borrow_mut().seek(SeekFrom::End(0))?);
+        dest.borrow_mut().seek(SeekFrom::Start(0))?;
+        self.to_bytes_with_flush(
             |i, buf| {
+                let mut dest = dest.borrow_mut();
                 dest.seek(SeekFrom::Start(i.0))?;
                 dest.write_all(buf)?;
                 Ok(())
             },
+            || Ok(dest.borrow_mut().flush()?),
             self.block_size,
             disk_size,
         )?;
         Ok(())
     }
 
-    /// Unique Disk UUID
-    pub fn uuid(&self) -> Uuid {
-        self.uuid
-    }
-
-    /// Slice of in-use partitions. Sorted by starting offset.
-    pub fn partitions(&self) -> &[Partition] {
-        self.partitions.as_slice()
-    }
-
-    /// Add a partition.
+    /// Write only what changed to `dest`.
+    ///
+    /// See [`GptC::to_bytes_incremental_with_func`] and
+    /// [`GptC::to_writer`] for details.
     ///
     /// # Errors
     ///
-    /// - If `part` overlaps with existing partitions
-    /// - In `no_std`, if `part` would overflow `C`.
-    pub fn add_partition(&mut self, part: Partition) -> Result<()> {
-        self.check_overlap(&part)?;
-        self.partitions.push(part)?;
-        self.partitions
-            .as_mut_slice()
-            .sort_unstable_by_key(|p| p.start());
+    /// - If all partitions do not fit within the usable blocks.
+    /// - [`Error::NotEnough`] if `dest` is too small.
+    /// - [`Error::Io`] if I/O does.
+    #[cfg(feature = "std")]
+    pub fn to_writer_incremental<RWS: Read + Write + Seek>(&self, dest: RWS) -> Result<()> {
+        let dest = core::cell::RefCell::new(dest);
+        let disk_size = Size::from_bytes(dest.borrow_mut().seek(SeekFrom::End(0))?);
+        self.to_bytes_incremental_with_func(
+            |i, buf| {
+                let mut dest = dest.borrow_mut();
+                dest.seek(SeekFrom::Start(i.0))?;
+                dest.read_exact(buf)?;
+                Ok(())
+            },
+            |i, buf| {
+                let mut dest = dest.borrow_mut();
+                dest.seek(SeekFrom::Start(i.0))?;
+                dest.write_all(buf)?;
+                Ok(())
+            },
+            || Ok(dest.borrow_mut().flush()?),
+            self.block_size,
+            disk_size,
+        )?;
         Ok(())
     }
 
-    /// Remove the partition at `index`.
-    pub fn remove_partition(&mut self, index: usize) -> Partition {
-        self.partitions.remove(index)
+    /// Read the Gpt from `device`, using its block size.
+    ///
+    /// See [`GptC::from_bytes`] for more details.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Invalid`] if the GPT is invalid, or the block size is larger
+    ///   than 4096 bytes.
+    /// - [`Error::NotEnough`] if `device` is too small.
+    /// - If `device` does.
+    pub fn from_device<D: BlockDevice>(mut device: D) -> Result<Self> {
+        device::check(&device)?;
+        let (block_size, disk_size) = (device.block_size(), device.size());
+        GptC::from_bytes_with_func(
+            |i, buf| device::read_at(&mut device, i, buf),
+            block_size,
+            disk_size,
+        )
     }
 
-    /// Set the disk UUID.
+    /// Write the Gpt to `device`.
     ///
-    /// WARNING: Gpt UUID's MUST be unique.
-    /// Only use this if you know what you're doing.
-    pub fn set_uuid(&mut self, uuid: Uuid) {
-        self.uuid = uuid;
+    /// `device` is flushed after each copy, see [`GptC::to_bytes_with_flush`].
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Invalid`] if the block size of `device` doesn't match, or is
+    ///   larger than 4096 bytes.
+    /// - If all partitions do not fit within the usable blocks.
+    /// - [`Error::NotEnough`] if `device` is too small.
+    /// - If `device` does.
+    pub fn to_device<D: BlockDevice>(&self, device: D) -> Result<()> {
+        let disk_size = self.check_device(&device)?;
+        let device = core::cell::RefCell::new(device);
+        self.to_bytes_with_flush(
+            |i, buf| device::write_at(&mut *device.borrow_mut(), i, buf),
+            || device.borrow_mut().flush(),
+            self.block_size,
+            disk_size,
+        )
+    }
+
+    /// Write only what changed to `device`.
+    ///
+    /// See [`GptC::to_bytes_incremental_with_func`] and
+    /// [`GptC::to_device`] for details.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Invalid`] if the block size of `device` doesn't match, or is
+    ///   larger than 4096 bytes.
+    /// - If all partitions do not fit within the usable blocks.
+    /// - [`Error::NotEnough`] if `device` is too small.
+    /// - If `device` does.
+    pub fn to_device_incremental<D: BlockDevice>(&self, device: D) -> Result<()> {
+        let disk_size = self.check_device(&device)?;
+        let device = core::cell::RefCell::new(device);
+        self.to_bytes_incremental_with_func(
+            |i, buf| device::read_at(&mut *device.borrow_mut(), i, buf),
+            |i, buf| device::write_at(&mut *device.borrow_mut(), i, buf),
+            || device.borrow_mut().flush(),
+            self.block_size,
+            disk_size,
+        )
+    }
+
+    /// Unique Disk UUID
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    /// The [`BlockSize`] this Gpt was created with
+    pub fn block_size(&self) -> BlockSize {
+        self.block_size
+    }
+
+    /// The disk [`Size`] this Gpt was created with
+    pub fn disk_size(&self) -> Size {
+        self.disk_size
+    }
+
+    /// Slice of in-use partitions. Sorted by starting offset.
+    pub fn partitions(&self) -> &[Partition] {
+        self.partitions.as_slice()
+    }
+
+    /// Check every partition is at least one block, within the usable range,
+    /// doesn't overlap another, and has a unique GUID.
+    ///
+    /// Partitions added through this type always are, but a table read from
+    /// a disk may not be.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::InvalidPartition`] if a partition ends before it starts, or
+    ///   its GUID is not unique.
+    /// - [`Error::Overlap`] if partitions overlap, or one is outside the usable
+    ///   range.
+    pub fn validate(&self) -> Result<()> {
+        for (i, part) in self.partitions().iter().enumerate() {
+            self.check_partition(part, i)?;
+            if self.partitions()[..i]
+                .iter()
+                .any(|p| p.uuid() == part.uuid())
+            {
+                return Err(Error::InvalidPartition("Partition GUID is not unique"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Add a partition.
+    ///
+    /// It goes in the slot given to [`PartitionBuilder::slot`], if any,
+    /// otherwise the first unused one.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::InvalidPartition`] if the partition ends before it starts, or
+    ///   its GUID is shared with another partition.
+    /// - [`Error::InvalidPartition`] if the slot is in use, or there are no
+    ///   unused slots.
+    /// - [`Error::Overlap`] if the partition overlaps with another partition,
+    ///   or is outside the usable range.
+    /// - In `no_std`, if `part` would overflow `C`.
+    ///
+    /// [`PartitionBuilder::slot`]: crate::PartitionBuilder::slot
+    pub fn add_partition(&mut self, mut part: Partition) -> Result<()> {
+        self.check_new_partition(&part, usize::MAX)?;
+        let slots = self.slots() as usize;
+        let slot = match part.slot() {
+            Some(slot) if slot >= slots => {
+                return Err(Error::InvalidPartition("Partition slot out of range"));
+            }
+            Some(slot) if self.index_of_slot(slot).is_some() => {
+                return Err(Error::InvalidPartition("Partition slot is in use"));
+            }
+            Some(slot) => slot,
+            None => (0..slots)
+                .find(|&slot| self.index_of_slot(slot).is_none())
+                .ok_or(Error::InvalidPartition("No unused partition slots"))?,
+        };
+        part.set_slot(Some(slot));
+        self.partitions.push(part)?;
+        self.partitions
+            .as_mut_slice()
+            .sort_unstable_by_key(|p| p.start());
+        Ok(())
+    }
+
+    /// Remove the partition at `index`.
+    pub fn remove_partition(&mut self, index: usize) -> Partition {
+        self.partitions.remove(index)
+    }
+
+    /// Modify the partition at `index` using `func`.
+    ///
+    /// `func` receives a copy of the partition,
+    /// which replaces the original only if the result is still valid.
+    /// The partition always keeps its slot.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::InvalidPartition`] if the partition is
+    ///   [`PartitionType::Unused`], or its GUID is shared with another
+    ///   partition.
+    /// - [`Error::Overlap`] if the partition would overlap with another
+    ///   partition, or be outside the usable range.
+    ///
+    /// # Panics
+    ///
+    /// - If `index` is out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// Change a partition to be used for LVM
+    ///
+    /// ```rust
+    /// # use parts::{Gpt, PartitionBuilder, PartitionType, uuid::Uuid, types::*};
+    /// # let mut gpt: Gpt = Gpt::new(Uuid::nil(), Size::from_mib(10), BlockSize::new(512));
+    /// # let part = PartitionBuilder::new(Uuid::from_u128(1), &gpt)
+    /// #     .start(gpt.first_usable())
+    /// #     .size(Size::from_mib(1))
+    /// #     .partition_type(PartitionType::LinuxFilesystemData)
+    /// #     .finish();
+    /// # gpt.add_partition(part).unwrap();
+    /// gpt.modify_partition(0, |part| {
+    ///     part.set_partition_type(PartitionType::LinuxLvm);
+    ///     part.set_name("pv0");
+    /// })
+    /// .unwrap();
+    /// assert_eq!(gpt.partitions()[0].name(), "pv0");
+    /// ```
+    ///
+    /// [`PartitionType::Unused`]: crate::PartitionType::Unused
+    pub fn modify_partition<F: FnOnce(&mut Partition)>(
+        &mut self,
+        index: usize,
+        func: F,
+    ) -> Result<()> {
+        let mut part = self.partitions()[index];
+        func(&mut part);
+        part.set_slot(self.partitions()[index].slot());
+        if part.partition_type() == PartitionType::Unused {
+            return Err(Error::InvalidPartition("Partition type must not be Unused"));
+        }
+        self.check_new_partition(&part, index)?;
+        self.partitions.as_mut_slice()[index] = part;
+        self.partitions
+            .as_mut_slice()
+            .sort_unstable_by_key(|p| p.start());
+        Ok(())
+    }
+
+    /// Resize the partition at `index` so that it ends at `end`, inclusive.
+    ///
+    /// This can both grow and shrink the partition.
+    /// The partition keeps its position, GUID, name, type and attributes.
+    ///
+    /// Note that only the partition entry is changed,
+    /// any data or filesystem inside the partition is **not** resized.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::InvalidPartition`] if `end` is before the partition start.
+    /// - [`Error::Overlap`] if the partition would overlap with its neighbours,
+    ///   or be outside the usable range.
+    ///
+    /// # Panics
+    ///
+    /// - If `index` is out of bounds.
+    pub fn resize_partition(&mut self, index: usize, end: Block) -> Result<()> {
+        let mut part = self.partitions()[index];
+        part.set_range(part.start(), end);
+        self.check_partition(&part, index)?;
+        self.partitions.as_mut_slice()[index] = part;
+        Ok(())
+    }
+
+    /// Grow the partition at `index` to fill all the free space after it,
+    /// up to the next partition or the last usable [`Block`].
+    ///
+    /// This is equivalent to `growpart`.
+    ///
+    /// See [`GptC::resize_partition`] for details.
+    ///
+    /// # Panics
+    ///
+    /// - If `index` is out of bounds.
+    pub fn grow_partition(&mut self, index: usize) -> Result<()> {
+        let end = self
+            .partitions()
+            .get(index + 1)
+            // Minus 1 because `end` is inclusive
+            .map(|p| p.start() - 1)
+            .unwrap_or_else(|| self.last_usable());
+        self.resize_partition(index, end)
+    }
+
+    /// Move the partition at `index` so that it starts at `start`,
+    /// keeping its size, GUID, name, type and attributes.
+    ///
+    /// Partitions are kept sorted by their starting offset,
+    /// so this may change the index of the partition.
+    ///
+    /// Note that only the partition entry is changed,
+    /// any data inside the partition is **not** moved.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Overlap`] if the partition would overlap with another
+    ///   partition, or be outside the usable range.
+    ///
+    /// # Panics
+    ///
+    /// - If `index` is out of bounds.
+    pub fn move_partition(&mut self, index: usize, start: Block) -> Result<()> {
+        let mut part = self.partitions()[index];
+        let len = part.end().0 - part.start().0;
+        let end = start.0.checked_add(len).map(Block).ok_or(Error::Overlap)?;
+        part.set_range(start, end);
+        self.check_partition(&part, index)?;
+        self.partitions.as_mut_slice()[index] = part;
+        self.partitions
+            .as_mut_slice()
+            .sort_unstable_by_key(|p| p.start());
+        Ok(())
+    }
+
+    /// Copy this table for a disk of `disk_size`, like `sgdisk -R` followed
+    /// by `sgdisk -G`.
+    ///
+    /// The disk and every partition get new random GUIDs.
+    ///
+    /// See [`GptC::replicate_with`] for details.
+    #[cfg(feature = "alloc")]
+    pub fn replicate(&self, disk_size: Size, block_size: BlockSize) -> Result<Self> {
+        self.replicate_with(disk_size, block_size, Uuid::new_v4)
+    }
+
+    /// Copy this table for a disk of `disk_size`, like `sgdisk -R` followed
+    /// by `sgdisk -G`.
+    ///
+    /// The disk and every partition get new GUIDs from `uuid`,
+    /// which is called for the disk first, then each partition in order.
+    ///
+    /// Partitions keep their byte range, slot, name, type and attributes.
+    /// If `block_size` is different their blocks are converted to it,
+    /// like [`GptC::rescale`].
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Invalid`] if `disk_size` is too small for a GPT.
+    /// - [`Error::NoSpace`] if the partitions don't fit in the usable range of
+    ///   the new disk.
+    /// - [`Error::InvalidPartition`] if `uuid` returns duplicate GUIDs, or a
+    ///   partition doesn't start or end on a `block_size` boundary.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use parts::{Gpt, uuid::Uuid, types::*};
+    /// let gpt: Gpt = Gpt::new(Uuid::nil(), Size::from_mib(10), BlockSize::new(512));
+    /// let mut next = 0;
+    /// let copy = gpt
+    ///     .replicate_with(Size::from_mib(20), BlockSize::new(512), || {
+    ///         next += 1;
+    ///         Uuid::from_u128(next)
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(copy.uuid(), Uuid::from_u128(1));
+    /// ```
+    pub fn replicate_with<F: FnMut() -> Uuid>(
+        &self,
+        disk_size: Size,
+        block_size: BlockSize,
+        mut uuid: F,
+    ) -> Result<Self> {
+        check_disk_size(block_size, disk_size)?;
+        let mut new = GptC {
+            uuid: uuid(),
+            partitions: C::new(),
+            disk_size,
+            block_size,
+        };
+        for part in self.partitions() {
+            let mut part = self.rescale_partition(part, block_size)?;
+            part.set_uuid(uuid());
+            if part.start() < new.first_usable() || part.end() > new.last_usable() {
+                return Err(Error::NoSpace);
+            }
+            if new.find_by_uuid(part.uuid()).is_some() {
+                return Err(Error::InvalidPartition("Partition GUID must be unique"));
+            }
+            new.partitions.push(part)?;
+        }
+        Ok(new)
+    }
+
+    /// Convert this table to use `block_size`, such as when moving an image
+    /// between 512e and 4Kn media.
+    ///
+    /// The disk size stays the same, and every partition keeps the same byte
+    /// range, in the new unit.
+    /// Header locations and the partition array size follow from the new
+    /// block size when the result is written.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Invalid`] if the disk size isn't a multiple of `block_size`,
+    ///   or is too small for a GPT.
+    /// - [`Error::InvalidPartition`] if a partition doesn't start or end on a
+    ///   `block_size` boundary.
+    /// - [`Error::Overlap`] if a partition is outside the new usable range.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use parts::{Gpt, PartitionBuilder, PartitionType, uuid::Uuid, types::*};
+    /// let mut gpt: Gpt = Gpt::new(Uuid::nil(), Size::from_mib(10), BlockSize::new(512));
+    /// let part = PartitionBuilder::new(Uuid::from_u128(1), &gpt)
+    ///     .start(Block(2048))
+    ///     .size(Size::from_mib(1))
+    ///     .partition_type(PartitionType::LinuxFilesystemData)
+    ///     .finish();
+    /// gpt.add_partition(part).unwrap();
+    ///
+    /// let gpt = gpt.rescale(BlockSize::new(4096)).unwrap();
+    /// assert_eq!(gpt.partitions()[0].start(), Block(256));
+    /// assert_eq!(gpt.partitions()[0].end(), Block(511));
+    /// ```
+    pub fn rescale(&self, block_size: BlockSize) -> Result<Self> {
+        if !self.disk_size.as_bytes().is_multiple_of(block_size.get()) {
+            return Err(Error::Invalid(
+                "Disk size is not a multiple of the block size",
+            ));
+        }
+        check_disk_size(block_size, self.disk_size)?;
+        let mut new = GptC {
+            uuid: self.uuid,
+            partitions: C::new(),
+            disk_size: self.disk_size,
+            block_size,
+        };
+        for part in self.partitions() {
+            let part = self.rescale_partition(part, block_size)?;
+            new.check_partition(&part, usize::MAX)?;
+            new.partitions.push(part)?;
+        }
+        Ok(new)
+    }
+
+    /// Set the disk UUID.
+    ///
+    /// WARNING: Gpt UUID's MUST be unique.
+    /// Only use this if you know what you're doing.
+    pub fn set_uuid(&mut self, uuid: Uuid) {
+        self.uuid = uuid;
     }
 
     /// The first usable partition [`Block`]
@@ -566,42 +1306,622 @@ impl<C: GptHelper<C>> Gpt<C> {
 
     /// Remaining usable partition space
     pub fn remaining(&self) -> Size {
+        let next = self.next_usable();
+        if next > self.last_usable() {
+            return Size::from_bytes(0);
+        }
         // Plus 1 block because inclusive?
-        (((self.last_usable() - self.next_usable().0) + 1) * self.block_size).into()
+        (((self.last_usable() - next.0) + 1) * self.block_size).into()
+    }
+
+    /// Find the partition with the unique identifer `uuid`.
+    ///
+    /// Returns the slot of the partition in the partition array, see
+    /// [`Partition::slot`], along with the partition.
+    /// Use [`GptC::index_of_slot`] to get its index in
+    /// [`GptC::partitions`].
+    pub fn find_by_uuid(&self, uuid: Uuid) -> Option<(usize, &Partition)> {
+        self.find(|p| p.uuid() == uuid)
+    }
+
+    /// Find the first partition named exactly `name`.
+    ///
+    /// See [`GptC::find_by_uuid`] for details.
+    pub fn find_by_name(&self, name: &str) -> Option<(usize, &Partition)> {
+        self.find(|p| p.name() == name)
+    }
+
+    /// Find the first partition named `name`, ignoring case.
+    ///
+    /// See [`GptC::find_by_uuid`] for details.
+    pub fn find_by_name_ignore_case(&self, name: &str) -> Option<(usize, &Partition)> {
+        self.find(|p| {
+            p.name()
+                .chars()
+                .flat_map(char::to_lowercase)
+                .eq(name.chars().flat_map(char::to_lowercase))
+        })
+    }
+
+    /// Iterate over all partitions of type `p_type`.
+    ///
+    /// See [`GptC::find_by_uuid`] for details.
+    pub fn find_by_type(
+        &self,
+        p_type: PartitionType,
+    ) -> impl Iterator<Item = (usize, &Partition)> + '_ {
+        self.partitions()
+            .iter()
+            .filter(move |p| p.partition_type() == p_type)
+            .map(|p| (slot(p), p))
+    }
+
+    /// Find the partition containing `block`.
+    ///
+    /// See [`GptC::find_by_uuid`] for details.
+    pub fn find_by_block(&self, block: Block) -> Option<(usize, &Partition)> {
+        self.find(|p| p.start() <= block && block <= p.end())
+    }
+
+    /// Find the partition containing the byte `offset`.
+    ///
+    /// See [`GptC::find_by_uuid`] for details.
+    pub fn find_by_offset(&self, offset: Offset) -> Option<(usize, &Partition)> {
+        self.find_by_block(offset / self.block_size)
+    }
+
+    /// Index in [`GptC::partitions`] of the partition in `slot`, if any.
+    ///
+    /// This is the index taken by methods such as
+    /// [`GptC::remove_partition`].
+    pub fn index_of_slot(&self, slot: usize) -> Option<usize> {
+        self.partitions()
+            .iter()
+            .position(|p| p.slot() == Some(slot))
+    }
+
+    /// Byte [`Offset`] of `part` on the disk.
+    pub fn partition_offset(&self, part: &Partition) -> Offset {
+        part.start() * self.block_size
+    }
+
+    /// [`Size`] of `part`, in bytes.
+    pub fn partition_size(&self, part: &Partition) -> Size {
+        // Plus 1 because `end` is inclusive
+        let end = (part.end() + 1) * self.block_size;
+        Size::from_bytes(end.0 - self.partition_offset(part).0)
+    }
+}
+
+// Private APIs
+impl<C: GptHelper<C>> GptC<C> {
+    /// Read the Gpt using `func`. See [`GptC::from_bytes_with_func`].
+    ///
+    /// If `hybrid`, the MBR only needs a valid signature, and the backup
+    /// header is read from where the primary says it is, rather than the
+    /// last block on the disk. Without a valid primary header, that's still
+    /// the last block.
+    ///
+    /// If the primary header or array is damaged, the backup is used instead.
+    /// Otherwise the backup is still checked, and must match the primary.
+    pub(crate) fn read_with_func<F: FnMut(Offset, &mut [u8]) -> Result<()>>(
+        mut func: F,
+        block_size: BlockSize,
+        disk_size: Size,
+        hybrid: bool,
+    ) -> Result<(Self, Status)> {
+        check_block_size(block_size)?;
+        {
+            let mut buf = ChunkBuf::new();
+            let buf = &read_block(&mut func, Block(0), block_size, &mut buf)?[..MBR_SIZE];
+            if hybrid {
+                ProtectiveMbr::check_signature(buf)?;
+            } else {
+                ProtectiveMbr::from_bytes(buf)?;
+            }
+        }
+        let last: Block = (disk_size / block_size) - 1;
+        let primary = Self::read_header(&mut func, Block(1), block_size).and_then(|primary| {
+            let valid = if hybrid {
+                Block(1) < primary.alt && primary.alt <= last
+            } else {
+                primary.alt == last
+            };
+            if !valid {
+                return Err(Error::Invalid("Primary header alternate location invalid"));
+            }
+            Ok(primary)
+        });
+        let alt_lba = primary.as_ref().map_or(last, |primary| primary.alt);
+        //
+        let mut partitions = C::new();
+        let primary = primary.and_then(|primary| {
+            Self::read_array(
+                &mut func,
+                &primary,
+                HeaderKind::Primary,
+                block_size,
+                (Block(1), alt_lba),
+                &mut partitions,
+            )?;
+            Ok(primary)
+        });
+        let alt = Self::read_header(&mut func, alt_lba, block_size).and_then(|alt| {
+            validate(
+                &alt,
+                HeaderKind::Backup,
+                &mut func,
+                block_size,
+                (alt_lba, Block(1)),
+                |_, _| Ok(()),
+            )?;
+            Ok(alt)
+        });
+        // An interrupted write leaves at most one copy damaged,
+        // so fall back to the backup. Only for damaged data, not I/O errors.
+        let (header, status) = match (primary, alt) {
+            (Ok(primary), Ok(alt)) => {
+                let status = if alt.uuid == primary.uuid
+                    && alt.partitions_crc32 == primary.partitions_crc32
+                {
+                    Status::Valid
+                } else {
+                    Status::BackupDamaged
+                };
+                (primary, status)
+            }
+            (Ok(primary), Err(e)) if damaged(&e) => (primary, Status::BackupDamaged),
+            (Err(e), Ok(alt)) if damaged(&e) => {
+                partitions = C::new();
+                Self::read_array(
+                    &mut func,
+                    &alt,
+                    HeaderKind::Backup,
+                    block_size,
+                    (alt_lba, Block(1)),
+                    &mut partitions,
+                )
+                // The primary error is the more useful one.
+                .map_err(|_| e)?;
+                (alt, Status::PrimaryDamaged)
+            }
+            (Err(e), _) | (_, Err(e)) => return Err(e),
+        };
+        partitions
+            .as_mut_slice()
+            .sort_unstable_by_key(|p| p.start());
+
+        let gpt = GptC {
+            uuid: header.uuid,
+            partitions,
+            disk_size,
+            block_size,
+        };
+        Ok((gpt, status))
+    }
+
+    /// Read the header at `lba`.
+    fn read_header<F: FnMut(Offset, &mut [u8]) -> Result<()>>(
+        func: &mut F,
+        lba: Block,
+        block_size: BlockSize,
+    ) -> Result<Header> {
+        // NOTE: `block_size - 92` is reserved and must be zero, but we don't check.
+        let mut buf = ChunkBuf::new();
+        Header::from_bytes(read_block(func, lba, block_size, &mut buf)?, block_size)
+    }
+
+    /// Validate `header` and read its partition array into `partitions`.
+    fn read_array<F: FnMut(Offset, &mut [u8]) -> Result<()>>(
+        func: &mut F,
+        header: &Header,
+        kind: HeaderKind,
+        block_size: BlockSize,
+        location: (Block, Block),
+        partitions: &mut C,
+    ) -> Result<()> {
+        validate(header, kind, func, block_size, location, |i, source| {
+            let mut part = Partition::from_bytes(source)?;
+            if part != Partition::new() {
+                part.set_slot(Some(i));
+                let _ = partitions.push(part);
+            }
+            Ok(())
+        })
+    }
+
+    /// Find the first partition matching `pred`, and its slot.
+    fn find<P: FnMut(&Partition) -> bool>(&self, mut pred: P) -> Option<(usize, &Partition)> {
+        self.partitions()
+            .iter()
+            .find(|p| pred(p))
+            .map(|p| (slot(p), p))
+    }
+
+    /// Copy of `part` with the same byte range in units of `block_size`.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::InvalidPartition`] if `part` doesn't start or end on a
+    ///   `block_size` boundary.
+    fn rescale_partition(&self, part: &Partition, block_size: BlockSize) -> Result<Partition> {
+        // Convert a byte offset, failing if it isn't on a block boundary.
+        let convert = |offset: Offset| {
+            if offset.0.is_multiple_of(block_size.get()) {
+                Ok(offset / block_size)
+            } else {
+                Err(Error::InvalidPartition(
+                    "Partition not aligned to the block size",
+                ))
+            }
+        };
+        let mut part = *part;
+        let start = convert(part.start() * self.block_size)?;
+        // Plus/Minus 1 because `end` is inclusive
+        let end = convert((part.end() + 1) * self.block_size)? - 1;
+        part.set_range(start, end);
+        Ok(part)
+    }
+
+    /// Check `device` can hold this Gpt, returning its size.
+    fn check_device<D: BlockDevice>(&self, device: &D) -> Result<Size> {
+        device::check(device)?;
+        if device.block_size() != self.block_size {
+            return Err(Error::Invalid("Block size mismatch"));
+        }
+        Ok(device.size())
+    }
+
+    /// Check that `part` is a valid replacement for the partition at `index`.
+    ///
+    /// It must be at least one block, within the usable range,
+    /// and not overlap any partition other than the one at `index`.
+    pub(crate) fn check_partition(&self, part: &Partition, index: usize) -> Result<()> {
+        if part.end() < part.start() {
+            return Err(Error::InvalidPartition("Partition ends before it starts"));
+        }
+        if part.start() < self.first_usable() || part.end() > self.last_usable() {
+            return Err(Error::Overlap);
+        }
+        for (i, existing) in self.partitions().iter().enumerate() {
+            if i == index {
+                continue;
+            }
+            if part.start() <= existing.end() && existing.start() <= part.end() {
+                return Err(Error::Overlap);
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`GptC::check_partition`], but `part` must also have a GUID not
+    /// used by any partition other than the one at `index`.
+    fn check_new_partition(&self, part: &Partition, index: usize) -> Result<()> {
+        self.check_partition(part, index)?;
+        if self
+            .partitions()
+            .iter()
+            .enumerate()
+            .any(|(i, p)| i != index && p.uuid() == part.uuid())
+        {
+            return Err(Error::InvalidPartition("Partition GUID must be unique"));
+        }
+        Ok(())
+    }
+
+    /// Write the Gpt using `func`, which also receives the [`Region`] being
+    /// written, calling `flush` after each copy.
+    /// See [`GptC::to_bytes_with_flush`].
+    fn write_regions<F, FL>(
+        &self,
+        mut func: F,
+        mut flush: FL,
+        block_size: BlockSize,
+        disk_size: Size,
+    ) -> Result<()>
+    where
+        F: FnMut(Region, Offset, &[u8]) -> Result<()>,
+        FL: FnMut() -> Result<()>,
+    {
+        check_block_size(block_size)?;
+        let last_lba = (disk_size / block_size) - 1;
+        let slots = self.slots();
+        let mut partition_buf = [0; PARTITION_ENTRY_SIZE as usize];
+        let mut digest = crc32::Digest::new(crc32::IEEE);
+        for i in 0..slots {
+            self.entry_bytes(i, &mut partition_buf)?;
+            digest.write(&partition_buf);
+        }
+        let parts_crc = digest.sum32();
+        let disk_uuid = self.uuid;
+
+        let alt = Header::new(
+            HeaderKind::Backup,
+            slots,
+            parts_crc,
+            disk_uuid,
+            block_size,
+            disk_size,
+        );
+        // Verify all partitions are within bounds
+        for part in self.partitions() {
+            let a = part.start();
+            let b = part.end();
+            if (a < alt.first_usable) || (b > alt.last_usable) {
+                return Err(Error::NotEnough);
+            }
+        }
+
+        {
+            let mbr = ProtectiveMbr::new(last_lba);
+            write_chunks(
+                &mut func,
+                Region::Mbr,
+                Offset(0),
+                block_size.get(),
+                block_size,
+                |pos, buf| match pos {
+                    0 => mbr.to_bytes(&mut buf[..MBR_SIZE]),
+                    _ => Ok(()),
+                },
+            )?;
+        }
+
+        self.write_header_array(
+            &mut func,
+            (Region::BackupHeader, Region::BackupArray),
+            alt,
+            last_lba,
+            block_size,
+        )?;
+        flush()?;
+
+        let primary = Header::new(
+            HeaderKind::Primary,
+            slots,
+            parts_crc,
+            disk_uuid,
+            block_size,
+            disk_size,
+        );
+        self.write_header_array(
+            func,
+            (Region::PrimaryHeader, Region::PrimaryArray),
+            primary,
+            Block(1),
+            block_size,
+        )?;
+        flush()?;
+        Ok(())
+    }
+
+    /// Number of entries in the written partition array.
+    ///
+    /// Always at least [`PARTITION_SLOTS`], so entries of removed partitions
+    /// are zeroed, and other tools see the usual 128.
+    fn slots(&self) -> u32 {
+        let len: u32 = self
+            .partitions()
+            .iter()
+            .map(|p| slot(p) + 1)
+            .max()
+            .unwrap_or(0)
+            .try_into()
+            .expect("Too many partitions, would overflow u32");
+        len.max(PARTITION_SLOTS)
+    }
+
+    /// Serialize entry `i` of the partition array into `buf`.
+    /// Unused slots are all zero.
+    fn entry_bytes(&self, i: u32, buf: &mut [u8]) -> Result<()> {
+        let part = self.index_of_slot(i as usize).map(|i| self.partitions()[i]);
+        match part {
+            Some(part) => part.to_bytes(buf),
+            None => {
+                buf.iter_mut().for_each(|b| *b = 0);
+                Ok(())
+            }
+        }
+    }
+
+    /// Serialize the partition array into `buf`, starting `pos` bytes in.
+    ///
+    /// Unused slots are zero.
+    fn array_bytes(&self, pos: u64, buf: &mut [u8]) -> Result<()> {
+        let entry_size = PARTITION_ENTRY_SIZE as usize;
+        let mut entry = [0; PARTITION_ENTRY_SIZE as usize];
+        let mut done = 0;
+        while done < buf.len() {
+            let at = pos + done as u64;
+            let i = (at / entry_size as u64).try_into().unwrap_or(u32::MAX);
+            let within = (at % entry_size as u64) as usize;
+            self.entry_bytes(i, &mut entry)?;
+            let len = (entry_size - within).min(buf.len() - done);
+            buf[done..][..len].copy_from_slice(&entry[within..][..len]);
+            done += len;
+        }
+        Ok(())
+    }
+
+    /// Write the partition array, then `header`.
+    fn write_header_array<F: FnMut(Region, Offset, &[u8]) -> Result<()>>(
+        &self,
+        mut func: F,
+        (header_region, array_region): (Region, Region),
+        header: Header,
+        last_lba: Block,
+        block_size: BlockSize,
+    ) -> Result<()> {
+        // The array first, so the header never refers to an unwritten array.
+        let array_len = u64::from(header.partitions) * u64::from(PARTITION_ENTRY_SIZE);
+        write_chunks(
+            &mut func,
+            array_region,
+            header.array * block_size,
+            whole_blocks(array_len, block_size),
+            block_size,
+            |pos, buf| self.array_bytes(pos, buf),
+        )?;
+        write_chunks(
+            &mut func,
+            header_region,
+            last_lba * block_size,
+            block_size.get(),
+            block_size,
+            |pos, buf| match pos {
+                0 => header.to_bytes(&mut buf[..HEADER_SIZE as usize]),
+                _ => Ok(()),
+            },
+        )?;
+        //
+        Ok(())
+    }
+}
+
+/// Serialized as the disk `uuid`, `block_size`, `disk_size`,
+/// `first_usable` and `last_usable` blocks, number of partition array `slots`,
+/// and the `partitions`.
+#[cfg(feature = "serde")]
+impl<C: GptHelper<C>> Serialize for GptC<C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("Gpt", 7)?;
+        s.serialize_field("uuid", &self.uuid)?;
+        s.serialize_field("block_size", &self.block_size)?;
+        s.serialize_field("disk_size", &self.disk_size)?;
+        s.serialize_field("first_usable", &self.first_usable())?;
+        s.serialize_field("last_usable", &self.last_usable())?;
+        s.serialize_field("slots", &self.slots())?;
+        s.serialize_field("partitions", self.partitions())?;
+        s.end()
+    }
+}
+
+/// The usable range is optional, but must match if present.
+/// So are the slots, which must be at least 128 and more than the slot of
+/// every partition.
+///
+/// The result is validated the same way as [`GptC::add_partition`],
+/// and partitions may be in any order.
+/// Partitions without a `slot` get the first unused one, in order.
+#[cfg(feature = "serde")]
+impl<'de, C: GptHelper<C>> Deserialize<'de> for GptC<C> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+        let repr = GptRepr::<C>::deserialize(deserializer)?;
+        GptC::from_repr(repr).map_err(de::Error::custom)
+    }
+}
+
+/// Unvalidated [`GptC`], for deserializing.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(crate = "serde_crate", rename = "Gpt", bound = "C: GptHelper<C>")]
+struct GptRepr<C> {
+    uuid: Uuid,
+    block_size: BlockSize,
+    disk_size: Size,
+    #[serde(default)]
+    first_usable: Option<Block>,
+    #[serde(default)]
+    last_usable: Option<Block>,
+    #[serde(default)]
+    slots: Option<u32>,
+    #[serde(default)]
+    partitions: Partitions<C>,
+}
+
+/// Partitions deserialized directly into `C`.
+#[cfg(feature = "serde")]
+struct Partitions<C>(C);
+
+#[cfg(feature = "serde")]
+impl<C: GptHelper<C>> Default for Partitions<C> {
+    fn default() -> Self {
+        Partitions(C::new())
     }
 }
 
-// Private APIs
-impl<C: GptHelper<C>> GptC<C> {
-    fn check_overlap(&mut self, part: &Partition) -> Result<()> {
-        for existing in self.partitions() {
-            if part.start() >= existing.start() && part.start() <= existing.end() {
-                return Err(Error::Overlap);
+#[cfg(feature = "serde")]
+impl<'de, C: GptHelper<C>> Deserialize<'de> for Partitions<C> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+        struct PartitionsVisitor<C>(PhantomData<C>);
+
+        impl<'de, C: GptHelper<C>> Visitor<'de> for PartitionsVisitor<C> {
+            type Value = Partitions<C>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a sequence of partitions")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> core::result::Result<Self::Value, A::Error> {
+                let mut partitions = C::new();
+                while let Some(part) = seq.next_element()? {
+                    partitions.push(part).map_err(de::Error::custom)?;
+                }
+                Ok(Partitions(partitions))
             }
         }
-        Ok(())
+
+        deserializer.deserialize_seq(PartitionsVisitor(PhantomData))
     }
+}
 
-    fn write_header_array<F: FnMut(Offset, &[u8]) -> Result<()>>(
-        &self,
-        mut func: F,
-        header: Header,
-        last_lba: Block,
-        block_size: BlockSize,
-    ) -> Result<()> {
-        let mut header_buf = [0; HEADER_SIZE as usize];
-        let mut partition_buf = [0; PARTITION_ENTRY_SIZE as usize];
-        //
-        header.to_bytes(&mut header_buf)?;
-        func(last_lba * block_size, &header_buf)?;
-        for (i, part) in self.partitions.as_slice().iter().enumerate() {
-            part.to_bytes(&mut partition_buf)?;
-            let b =
-                Offset((header.array * block_size).0 + ((PARTITION_ENTRY_SIZE as u64) * i as u64));
-            func(b, &partition_buf)?;
+#[cfg(feature = "serde")]
+impl<C: GptHelper<C>> GptC<C> {
+    /// Validate a deserialized [`GptC`].
+    fn from_repr(repr: GptRepr<C>) -> Result<Self> {
+        let block_size = repr.block_size;
+        if !repr.disk_size.as_bytes().is_multiple_of(block_size.get()) {
+            return Err(Error::Invalid(
+                "Disk size is not a multiple of the block size",
+            ));
         }
-        //
-        Ok(())
+        check_disk_size(block_size, repr.disk_size)?;
+        let (first, last) = Header::usable(block_size, repr.disk_size);
+        if repr.first_usable.unwrap_or(first) != first || repr.last_usable.unwrap_or(last) != last {
+            return Err(Error::Invalid("Usable range does not match disk size"));
+        }
+        let slots = repr.slots.unwrap_or(PARTITION_SLOTS);
+        if slots < PARTITION_SLOTS {
+            return Err(Error::Invalid("Unsupported number of partition slots"));
+        }
+        let slots = slots as usize;
+        let mut gpt = GptC {
+            uuid: repr.uuid,
+            partitions: repr.partitions.0,
+            disk_size: repr.disk_size,
+            block_size,
+        };
+        if gpt.partitions().len() > slots {
+            return Err(Error::Invalid("More partitions than slots"));
+        }
+        for (i, part) in gpt.partitions().iter().enumerate() {
+            match part.slot() {
+                Some(slot) if slot >= slots => {
+                    return Err(Error::InvalidPartition("Partition slot out of range"));
+                }
+                Some(slot) if gpt.partitions()[..i].iter().any(|p| p.slot() == Some(slot)) => {
+                    return Err(Error::InvalidPartition("Partition slot is not unique"));
+                }
+                _ => (),
+            }
+        }
+        // Partitions without a slot get the first unused one, in order.
+        for i in 0..gpt.partitions().len() {
+            if gpt.partitions()[i].slot().is_none() {
+                let slot = (0..slots)
+                    .find(|&slot| gpt.index_of_slot(slot).is_none())
+                    .expect("No more partitions than slots");
+                gpt.partitions.as_mut_slice()[i].set_slot(Some(slot));
+            }
+        }
+        gpt.partitions
+            .as_mut_slice()
+            .sort_unstable_by_key(|p| p.start());
+        gpt.validate()?;
+        Ok(gpt)
     }
 }
 
@@ -638,6 +1958,10 @@ mod test_no_std {
     #[cfg(feature = "std")]
     assert_eq_size!(Gpt, [u8; 32 + mem::size_of::<Vec::<Partition>>()]);
 
+    // I/O buffers are aligned for direct I/O
+    assert_eq_size!(ChunkBuf, [u8; CHUNK_SIZE]);
+    const_assert_eq!(mem::align_of::<ChunkBuf>(), CHUNK_SIZE);
+
     //
     #[cfg(not(feature = "std"))]
     assert_eq_size!(
@@ -725,7 +2049,7 @@ mod test_no_std {
         gpt.add_partition(part).unwrap();
         assert_eq!(gpt.next_usable(), Block(35));
 
-        let part = PartitionBuilder::new(Uuid::nil(), &gpt)
+        let part = PartitionBuilder::new(Uuid::from_u128(1), &gpt)
             .start(gpt.next_usable_aligned())
             .end(gpt.next_usable_aligned())
             .finish();
@@ -786,12 +2110,263 @@ mod test_no_std {
         //
         Ok(())
     }
+
+    /// Create a Gpt with two 1MiB partitions, with a 1MiB gap between them.
+    fn two_parts() -> Gpt {
+        let mut gpt: Gpt = Gpt::new(Uuid::nil(), Size::from_mib(10), BLOCK_SIZE);
+        let part = PartitionBuilder::new(Uuid::from_u128(1), &gpt)
+            .start(Block(2048))
+            .size(Size::from_mib(1))
+            .partition_type(PartitionType::LinuxFilesystemData)
+            .name("First")
+            .finish();
+        gpt.add_partition(part).unwrap();
+        let part = PartitionBuilder::new(Uuid::from_u128(2), &gpt)
+            .start(Block(6144))
+            .size(Size::from_mib(1))
+            .partition_type(PartitionType::LinuxSwap)
+            .finish();
+        gpt.add_partition(part).unwrap();
+        gpt
+    }
+
+    /// Partitions can grow and shrink, keeping everything else
+    #[test]
+    fn resize_partition() -> Result {
+        let mut gpt = two_parts();
+        gpt.resize_partition(0, Block(6143))?;
+        let part = gpt.partitions()[0];
+        assert_eq!(part.start(), Block(2048));
+        assert_eq!(part.end(), Block(6143));
+        assert_eq!(part.uuid(), Uuid::from_u128(1));
+        assert_eq!(part.name(), "First");
+        assert_eq!(part.partition_type(), PartitionType::LinuxFilesystemData);
+        //
+        gpt.resize_partition(0, Block(2048))?;
+        assert_eq!(gpt.partitions()[0].end(), Block(2048));
+        //
+        match gpt.resize_partition(0, Block(6144)) {
+            Err(Error::Overlap) => (),
+            e => panic!("Overlapping resize was allowed: {:?}", e),
+        }
+        match gpt.resize_partition(0, Block(2047)) {
+            Err(Error::InvalidPartition(_)) => (),
+            e => panic!("Negative size was allowed: {:?}", e),
+        }
+        match gpt.resize_partition(1, gpt.last_usable() + 1) {
+            Err(Error::Overlap) => (),
+            e => panic!("Resize past usable range was allowed: {:?}", e),
+        }
+        Ok(())
+    }
+
+    /// Growing fills the space up to the next partition or the end.
+    #[test]
+    fn grow_partition() -> Result {
+        let mut gpt = two_parts();
+        gpt.grow_partition(0)?;
+        assert_eq!(gpt.partitions()[0].end(), Block(6143));
+        gpt.grow_partition(1)?;
+        assert_eq!(gpt.partitions()[1].end(), gpt.last_usable());
+        assert_eq!(gpt.remaining(), Size::from_bytes(0));
+        // Nothing left to grow into
+        gpt.grow_partition(1)?;
+        assert_eq!(gpt.partitions()[1].end(), gpt.last_usable());
+        Ok(())
+    }
+
+    /// Moving keeps the size, and re-sorts the partitions.
+    #[test]
+    fn move_partition() -> Result {
+        let mut gpt = two_parts();
+        let size = gpt.partitions()[1].end().0 - gpt.partitions()[1].start().0;
+        gpt.move_partition(1, Block(4096))?;
+        assert_eq!(gpt.partitions()[1].start(), Block(4096));
+        assert_eq!(gpt.partitions()[1].end(), Block(4096 + size));
+        //
+        match gpt.move_partition(1, Block(3072)) {
+            Err(Error::Overlap) => (),
+            e => panic!("Overlapping move was allowed: {:?}", e),
+        }
+        match gpt.move_partition(0, Block(0)) {
+            Err(Error::Overlap) => (),
+            e => panic!("Move before usable range was allowed: {:?}", e),
+        }
+        // Move the first partition after the second
+        gpt.move_partition(0, Block(8192))?;
+        assert_eq!(gpt.partitions()[0].uuid(), Uuid::from_u128(2));
+        assert_eq!(gpt.partitions()[1].uuid(), Uuid::from_u128(1));
+        assert_eq!(gpt.partitions()[1].start(), Block(8192));
+        Ok(())
+    }
+
+    /// Modifications are validated before being applied
+    #[test]
+    fn modify_partition() -> Result {
+        let mut gpt = two_parts();
+        gpt.modify_partition(0, |p| {
+            p.set_name("Renamed");
+            p.set_partition_type(PartitionType::LinuxLvm);
+            p.set_attributes(1);
+        })?;
+        let part = gpt.partitions()[0];
+        assert_eq!(part.name(), "Renamed");
+        assert_eq!(part.partition_type(), PartitionType::LinuxLvm);
+        assert_eq!(part.attributes(), 1);
+        //
+        match gpt.modify_partition(0, |p| p.set_uuid(Uuid::from_u128(2))) {
+            Err(Error::InvalidPartition(_)) => (),
+            e => panic!("Duplicate GUID was allowed: {:?}", e),
+        }
+        match gpt.modify_partition(0, |p| p.set_partition_type(PartitionType::Unused)) {
+            Err(Error::InvalidPartition(_)) => (),
+            e => panic!("Unused type was allowed: {:?}", e),
+        }
+        let grown = PartitionBuilder::from_partition(&gpt.partitions()[0], &gpt)
+            .size(Size::from_mib(4))
+            .finish();
+        match gpt.modify_partition(0, |p| *p = grown) {
+            Err(Error::Overlap) => (),
+            e => panic!("Overlapping partition was allowed: {:?}", e),
+        }
+        // Failed modifications must not change anything
+        assert_eq!(gpt.partitions()[0], part);
+        Ok(())
+    }
+
+    /// Builders can start from an existing partition
+    #[test]
+    fn builder_from_partition() {
+        let gpt = two_parts();
+        let part = gpt.partitions()[0];
+        let copy = PartitionBuilder::from_partition(&part, &gpt).finish();
+        assert_eq!(copy.slot(), None);
+        let copy = PartitionBuilder::from_partition(&part, &gpt)
+            .slot(0)
+            .finish();
+        assert_eq!(copy, part);
+        //
+        let copy = PartitionBuilder::from_partition(&part, &gpt)
+            .uuid(Uuid::from_u128(3))
+            .name("Copy")
+            .attributes(4)
+            .finish();
+        assert_eq!(copy.uuid(), Uuid::from_u128(3));
+        assert_eq!(copy.name(), "Copy");
+        assert_eq!(copy.attributes(), 4);
+        assert_eq!(copy.start(), part.start());
+        assert_eq!(copy.end(), part.end());
+        assert_eq!(copy.partition_type(), part.partition_type());
+        // Any container
+        let array: Gpt<DefArray> = Gpt::new(Uuid::nil(), Size::from_mib(10), BLOCK_SIZE);
+        let copy = PartitionBuilder::from_partition(&part, &array)
+            .slot(0)
+            .finish();
+        assert_eq!(copy, part);
+    }
+
+    /// Partitions can be found by their properties
+    #[test]
+    fn find_partitions() {
+        let gpt = two_parts();
+        let (i, part) = gpt.find_by_uuid(Uuid::from_u128(2)).unwrap();
+        assert_eq!(i, 1);
+        assert_eq!(part.partition_type(), PartitionType::LinuxSwap);
+        assert!(gpt.find_by_uuid(Uuid::from_u128(3)).is_none());
+        //
+        assert_eq!(gpt.find_by_name("First").unwrap().0, 0);
+        assert!(gpt.find_by_name("first").is_none());
+        assert_eq!(gpt.find_by_name_ignore_case("fIRST").unwrap().0, 0);
+        assert!(gpt.find_by_name_ignore_case("firs").is_none());
+        //
+        let mut swap = gpt.find_by_type(PartitionType::LinuxSwap);
+        assert_eq!(swap.next().unwrap().0, 1);
+        assert!(swap.next().is_none());
+        assert_eq!(gpt.find_by_type(PartitionType::LinuxLvm).count(), 0);
+        //
+        assert_eq!(gpt.find_by_block(Block(2048)).unwrap().0, 0);
+        assert_eq!(gpt.find_by_block(Block(4095)).unwrap().0, 0);
+        assert!(gpt.find_by_block(Block(4096)).is_none());
+        assert_eq!(gpt.find_by_block(Block(6144)).unwrap().0, 1);
+        assert_eq!(gpt.find_by_offset(Offset(4096 * 512 - 1)).unwrap().0, 0);
+        assert!(gpt.find_by_offset(Offset(4096 * 512)).is_none());
+    }
+
+    /// Replicas keep the layout, with new GUIDs
+    #[test]
+    fn replicate() -> Result {
+        let gpt = two_parts();
+        let mut next = 10;
+        let mut uuid = || {
+            next += 1;
+            Uuid::from_u128(next)
+        };
+        let copy = gpt.replicate_with(Size::from_mib(20), BLOCK_SIZE, &mut uuid)?;
+        assert_eq!(copy.uuid(), Uuid::from_u128(11));
+        assert_eq!(copy.disk_size(), Size::from_mib(20));
+        assert_eq!(copy.partitions().len(), 2);
+        for (i, (new, old)) in copy.partitions().iter().zip(gpt.partitions()).enumerate() {
+            assert_eq!(new.uuid(), Uuid::from_u128(12 + i as u128));
+            assert_eq!(new.start(), old.start());
+            assert_eq!(new.end(), old.end());
+            assert_eq!(new.name(), old.name());
+            assert_eq!(new.partition_type(), old.partition_type());
+        }
+        // Exactly big enough
+        let size = Size::from_bytes((copy.partitions()[1].end().0 + 34) * BLOCK_SIZE.get());
+        gpt.replicate_with(size, BLOCK_SIZE, &mut uuid)?;
+        //
+        match gpt.replicate_with(size - BLOCK_SIZE, BLOCK_SIZE, &mut uuid) {
+            Err(Error::NoSpace) => (),
+            e => panic!("Replicated onto a disk too small: {:?}", e),
+        }
+        // Different block sizes keep the same byte ranges
+        let large = gpt.replicate_with(Size::from_mib(20), BlockSize::new(4096), &mut uuid)?;
+        for (new, old) in large.partitions().iter().zip(gpt.partitions()) {
+            assert_eq!(large.partition_offset(new), gpt.partition_offset(old));
+            assert_eq!(large.partition_size(new), gpt.partition_size(old));
+            assert_eq!(new.slot(), old.slot());
+        }
+        match gpt.replicate_with(Size::from_mib(20), BLOCK_SIZE, Uuid::nil) {
+            Err(Error::InvalidPartition(_)) => (),
+            e => panic!("Replicated with duplicate GUIDs: {:?}", e),
+        }
+        Ok(())
+    }
+
+    /// Partitions keep their byte ranges in the new block size
+    #[test]
+    fn rescale() -> Result {
+        let gpt = two_parts();
+        let large = gpt.rescale(BlockSize::new(4096))?;
+        assert_eq!(large.block_size(), BlockSize::new(4096));
+        assert_eq!(large.disk_size(), gpt.disk_size());
+        assert_eq!(large.first_usable(), Block(6));
+        for (new, old) in large.partitions().iter().zip(gpt.partitions()) {
+            assert_eq!(new.start() * BlockSize::new(4096), old.start() * BLOCK_SIZE);
+            assert_eq!(
+                (new.end() + 1) * BlockSize::new(4096),
+                (old.end() + 1) * BLOCK_SIZE
+            );
+            assert_eq!(new.uuid(), old.uuid());
+        }
+        assert_eq!(large.rescale(BLOCK_SIZE)?, gpt);
+        //
+        let mut gpt = gpt;
+        gpt.resize_partition(1, Block(6144 + 8))?;
+        match gpt.rescale(BlockSize::new(4096)) {
+            Err(Error::InvalidPartition(_)) => (),
+            e => panic!("Unaligned partition was rescaled: {:?}", e),
+        }
+        Ok(())
+    }
 }
 
 #[cfg(all(test, feature = "std"))]
 mod test {
     use super::*;
     use crate::{
+        hybrid,
         partitions::PartitionType,
         util::{Result, *},
     };
@@ -860,14 +2435,224 @@ mod test {
         Ok(())
     }
 
+    /// Missing both headers is an error, reported for the primary
     #[test]
     #[should_panic = "Invalid Signature"]
     fn missing_gpt_test() {
         let mut raw = data().unwrap();
         raw[512..][..512].copy_from_slice(&[0; 512]);
+        let last = raw.len() - 512;
+        raw[last..].copy_from_slice(&[0; 512]);
         let _gpt = read_gpt_size::<Vec>(&raw).unwrap();
     }
 
+    /// A Gpt with two partitions, written to a 10MiB image.
+    fn written_gpt() -> Result<(Gpt, StdVec<u8>)> {
+        let mut gpt: Gpt = Gpt::new(Uuid::new_v4(), Size::from_mib(10), BLOCK_SIZE);
+        for (i, start) in [2048, 4096].iter().enumerate() {
+            let part = PartitionBuilder::new(Uuid::from_u128(i as u128 + 1), &gpt)
+                .start(Block(*start))
+                .size(Size::from_mib(1))
+                .partition_type(PartitionType::LinuxFilesystemData)
+                .finish();
+            gpt.add_partition(part)?;
+        }
+        let mut raw = vec![0; TEN_MIB_BYTES];
+        gpt.to_bytes(&mut raw)?;
+        Ok((gpt, raw))
+    }
+
+    /// Partitions keep their slot, even with unused slots before them
+    #[test]
+    fn slots() -> Result {
+        let (mut gpt, _) = written_gpt()?;
+        let part = PartitionBuilder::new(Uuid::from_u128(3), &gpt)
+            .start(Block(6144))
+            .size(Size::from_mib(1))
+            .partition_type(PartitionType::LinuxSwap)
+            .finish();
+        gpt.add_partition(part)?;
+        gpt.remove_partition(1);
+        let mut raw = vec![0; TEN_MIB_BYTES];
+        gpt.to_bytes(&mut raw)?;
+        // Slot 1 is zeroed, slot 2 is still used
+        assert!(raw[1024 + 128..][..128].iter().all(|b| *b == 0));
+        assert!(raw[1024 + 256..][..128].iter().any(|b| *b != 0));
+        //
+        let mut gpt: Gpt = Gpt::from_bytes(&raw, BLOCK_SIZE)?;
+        let slots: StdVec<_> = gpt.partitions().iter().map(|p| p.slot()).collect();
+        assert_eq!(slots, [Some(0), Some(2)]);
+        assert_eq!(gpt.find_by_uuid(Uuid::from_u128(3)).unwrap().0, 2);
+        assert_eq!(
+            gpt.find_by_type(PartitionType::LinuxSwap).next().unwrap().0,
+            2
+        );
+        assert_eq!(gpt.index_of_slot(2), Some(1));
+        assert_eq!(gpt.index_of_slot(1), None);
+        // Explicit slots, then the first unused one
+        let part = PartitionBuilder::new(Uuid::from_u128(4), &gpt)
+            .start(Block(10240))
+            .size(Size::from_mib(1))
+            .partition_type(PartitionType::LinuxSwap)
+            .slot(2)
+            .finish();
+        match gpt.add_partition(part) {
+            Err(Error::InvalidPartition(_)) => (),
+            e => panic!("Slot in use was allowed: {:?}", e),
+        }
+        gpt.add_partition(
+            PartitionBuilder::from_partition(&part, &gpt)
+                .slot(5)
+                .finish(),
+        )?;
+        assert_eq!(gpt.find_by_uuid(Uuid::from_u128(4)).unwrap().0, 5);
+        let part = PartitionBuilder::from_partition(&part, &gpt)
+            .uuid(Uuid::from_u128(5))
+            .start(Block(12288))
+            .size(Size::from_mib(1))
+            .finish();
+        gpt.add_partition(part)?;
+        assert_eq!(gpt.find_by_uuid(Uuid::from_u128(5)).unwrap().0, 1);
+        Ok(())
+    }
+
+    /// Read `raw` with [`GptC::from_bytes_with_status`], failing reads of
+    /// the primary array with [`Error::NotEnough`] if `short`.
+    fn read_status(raw: &[u8], short: bool) -> Result<(Gpt, Status)> {
+        Ok(Gpt::from_bytes_with_status(
+            |offset, buf| {
+                let offset = offset.0 as usize;
+                if short && (1024..1024 + 16384).contains(&offset) {
+                    return Err(Error::NotEnough);
+                }
+                buf.copy_from_slice(&raw[offset..][..buf.len()]);
+                Ok(())
+            },
+            BLOCK_SIZE,
+            Size::from_bytes(raw.len() as u64),
+        )?)
+    }
+
+    /// Test that either damaged copy is recovered from the other
+    #[test]
+    fn recover() -> Result {
+        let (gpt, raw) = written_gpt()?;
+        let last = TEN_MIB_BYTES - 512;
+        let backup_array = last - (32 * 512);
+        assert_eq!(read_status(&raw, false)?, (gpt.clone(), Status::Valid));
+        // Header, then array, for each copy
+        for &(offset, status) in &[
+            (512, Status::PrimaryDamaged),
+            (1024, Status::PrimaryDamaged),
+            (last, Status::BackupDamaged),
+            (backup_array, Status::BackupDamaged),
+        ] {
+            let mut raw = raw.clone();
+            raw[offset + 40] ^= 0xFF;
+            let new: Gpt = Gpt::from_bytes(&raw, BLOCK_SIZE)?;
+            assert_eq!(new, gpt, "Damage at {} not recovered", offset);
+            assert_eq!(read_status(&raw, false)?.1, status, "Damage at {}", offset);
+        }
+        // A primary array that can't be read in full
+        assert_eq!(
+            read_status(&raw, true)?,
+            (gpt.clone(), Status::PrimaryDamaged)
+        );
+        // A valid backup of a different Gpt
+        let mut other = gpt.clone();
+        other.remove_partition(0);
+        let mut stale = vec![0; TEN_MIB_BYTES];
+        other.to_bytes(&mut stale)?;
+        stale[..1024 + 16384].copy_from_slice(&raw[..1024 + 16384]);
+        assert_eq!(
+            read_status(&stale, false)?,
+            (gpt.clone(), Status::BackupDamaged)
+        );
+        // Both copies
+        let mut raw = raw;
+        raw[1024 + 40] ^= 0xFF;
+        raw[backup_array + 40] ^= 0xFF;
+        let err = <Gpt>::from_bytes(&raw, BLOCK_SIZE).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::Invalid("Primary Partition Array CRC32 mismatch")
+            ),
+            "{}",
+            err
+        );
+        Ok(())
+    }
+
+    /// Test that interrupting a write at any point, with any of the writes
+    /// since the last flush lost, leaves either the old or new Gpt readable.
+    #[test]
+    fn interrupted_write() -> Result {
+        let (old, raw) = written_gpt()?;
+        let mut new = old.clone();
+        new.remove_partition(0);
+        new.modify_partition(0, |p| p.set_name("New"))?;
+        new.add_partition(
+            PartitionBuilder::new(Uuid::from_u128(3), &new)
+                .start(Block(8192))
+                .size(Size::from_mib(1))
+                .partition_type(PartitionType::LinuxSwap)
+                .finish(),
+        )?;
+        // Writes, split into phases by flushes
+        let writes = core::cell::RefCell::new(vec![StdVec::new()]);
+        new.to_bytes_with_flush(
+            |offset, buf| {
+                let mut writes = writes.borrow_mut();
+                writes.last_mut().unwrap().push((offset, buf.to_vec()));
+                Ok(())
+            },
+            || {
+                writes.borrow_mut().push(StdVec::new());
+                Ok(())
+            },
+            BLOCK_SIZE,
+            Size::from_mib(10),
+        )?;
+        let mut phases = writes.into_inner();
+        assert_eq!(phases.pop(), Some(StdVec::new()), "Must end with a flush");
+        assert_eq!(phases.len(), 2, "Backup and primary copies");
+        // Headers last, after their arrays
+        let last = Offset(TEN_MIB_BYTES as u64 - 512);
+        assert_eq!(phases[0].last().map(|w| w.0), Some(last));
+        assert_eq!(phases[1].last().map(|w| w.0), Some(Offset(512)));
+        //
+        for (phase, writes) in phases.iter().enumerate() {
+            let mut base = raw.clone();
+            for (offset, buf) in phases[..phase].iter().flatten() {
+                base[offset.0 as usize..][..buf.len()].copy_from_slice(buf);
+            }
+            // Every subset of the unflushed writes.
+            // Writes of what is already there can't make a difference.
+            let writes: StdVec<_> = writes
+                .iter()
+                .filter(|(offset, buf)| base[offset.0 as usize..][..buf.len()] != buf[..])
+                .collect();
+            assert!(writes.len() < 16, "Too many writes to try every subset");
+            for mask in 0..(1u32 << writes.len()) {
+                let mut raw = base.clone();
+                for (i, (offset, buf)) in writes.iter().enumerate() {
+                    if mask & (1 << i) != 0 {
+                        raw[offset.0 as usize..][..buf.len()].copy_from_slice(buf);
+                    }
+                }
+                let gpt: Gpt = Gpt::from_bytes(&raw, BLOCK_SIZE)?;
+                assert!(
+                    gpt == old || gpt == new,
+                    "Phase {} with writes {:b} is neither old nor new",
+                    phase,
+                    mask
+                );
+            }
+        }
+        Ok(())
+    }
+
     /// Test that the from_reader/to_writer methods work correctly
     #[test]
     fn reader_writer() -> Result {
@@ -962,4 +2747,397 @@ mod test {
         //
         Ok(())
     }
+
+    /// Test that random replicas get new GUIDs
+    #[test]
+    fn replicate_random() -> Result {
+        let mut gpt: Gpt = Gpt::new(Uuid::new_v4(), Size::from_mib(10), BLOCK_SIZE);
+        let part = PartitionBuilder::new(Uuid::new_v4(), &gpt)
+            .start(Block(2048))
+            .size(Size::from_mib(1))
+            .partition_type(PartitionType::LinuxFilesystemData)
+            .finish();
+        gpt.add_partition(part)?;
+        let copy = gpt.replicate(Size::from_mib(10), BLOCK_SIZE)?;
+        assert_ne!(copy.uuid(), gpt.uuid());
+        assert_ne!(copy.partitions()[0].uuid(), part.uuid());
+        assert_eq!(copy.partitions()[0].start(), part.start());
+        Ok(())
+    }
+
+    /// Test that a rescaled Gpt can be written and read with the new block
+    /// size
+    #[test]
+    fn rescale_write() -> Result {
+        let block_size = BlockSize::new(4096);
+        let mut gpt: Gpt = Gpt::new(Uuid::new_v4(), Size::from_mib(10), BLOCK_SIZE);
+        let part = PartitionBuilder::new(Uuid::new_v4(), &gpt)
+            .start(Block(2048))
+            .size(Size::from_mib(1))
+            .partition_type(PartitionType::LinuxFilesystemData)
+            .finish();
+        gpt.add_partition(part)?;
+        let gpt = gpt.rescale(block_size)?;
+        let mut data = io::Cursor::new(vec![0; TEN_MIB_BYTES]);
+        gpt.to_writer(&mut data)?;
+        let read: Gpt = Gpt::from_reader(&mut data, block_size)?;
+        assert_eq!(read, gpt);
+        Ok(())
+    }
+
+    /// Test that the write plan describes exactly what is written
+    #[test]
+    fn write_plan() -> Result {
+        let (gpt, _) = written_gpt()?;
+        let plan = gpt.write_plan(BLOCK_SIZE, Size::from_mib(10))?;
+        let last = TEN_MIB_BYTES as u64 - 512;
+        let expected = [
+            (Region::Mbr, 0, 512),
+            (Region::BackupArray, last - (32 * 512), 128 * 128),
+            (Region::BackupHeader, last, 512),
+            (Region::PrimaryArray, 1024, 128 * 128),
+            (Region::PrimaryHeader, 512, 512),
+        ];
+        let expected: StdVec<_> = expected
+            .iter()
+            .map(|&(region, offset, len)| PlannedWrite {
+                region,
+                offset: Offset(offset),
+                len: Size::from_bytes(len),
+            })
+            .collect();
+        assert_eq!(plan, expected);
+        // Every write is covered by the plan, and nothing else
+        let mut written = 0;
+        gpt.to_bytes_with_func(
+            |offset, buf| {
+                let end = offset.0 + buf.len() as u64;
+                assert!(
+                    plan.iter()
+                        .any(|w| w.offset <= offset && end <= w.offset.0 + w.len.as_bytes()),
+                    "Unplanned write at {}",
+                    offset
+                );
+                written += buf.len() as u64;
+                Ok(())
+            },
+            BLOCK_SIZE,
+            Size::from_mib(10),
+        )?;
+        assert_eq!(written, plan.iter().map(|w| w.len.as_bytes()).sum::<u64>());
+        Ok(())
+    }
+
+    /// Test that all I/O is in whole, aligned blocks, and reads are few
+    #[test]
+    fn aligned_io() -> Result {
+        for &size in &[512, 4096] {
+            let block_size = BlockSize::new(size);
+            let disk_size = Size::from_mib(10);
+            let aligned = |offset: Offset, len: usize| {
+                assert_eq!(offset.0 % size, 0, "Unaligned offset {}", offset);
+                assert_eq!(len as u64 % size, 0, "Partial block at {}", offset);
+                assert!(len <= CHUNK_SIZE, "Large buffer at {}", offset);
+            };
+            let gpt: Gpt = Gpt::new(Uuid::new_v4(), disk_size, block_size);
+            let mut raw = vec![0; TEN_MIB_BYTES];
+            gpt.to_bytes_with_func(
+                |offset, buf| {
+                    aligned(offset, buf.len());
+                    raw[offset.0 as usize..][..buf.len()].copy_from_slice(buf);
+                    Ok(())
+                },
+                block_size,
+                disk_size,
+            )?;
+            let mut reads = 0;
+            let new: Gpt = Gpt::from_bytes_with_func(
+                |offset, buf| {
+                    aligned(offset, buf.len());
+                    reads += 1;
+                    buf.copy_from_slice(&raw[offset.0 as usize..][..buf.len()]);
+                    Ok(())
+                },
+                block_size,
+                disk_size,
+            )?;
+            assert_eq!(new, gpt);
+            // MBR, then a header and the 16KiB array for each copy
+            assert_eq!(
+                reads,
+                1 + 2 * (1 + (128 * 128 / CHUNK_SIZE)),
+                "Block size {}",
+                size
+            );
+        }
+        // Larger blocks don't fit in a single read or write
+        let block_size = BlockSize::new(8192);
+        let disk_size = Size::from_mib(10);
+        let gpt: Gpt = Gpt::new(Uuid::new_v4(), disk_size, block_size);
+        let err = gpt
+            .to_bytes_with_func(|_, _| panic!("Wrote"), block_size, disk_size)
+            .unwrap_err();
+        assert!(matches!(err, Error::Invalid(_)), "{}", err);
+        let err =
+            <Gpt>::from_bytes_with_func(|_, _| panic!("Read"), block_size, disk_size).unwrap_err();
+        assert!(matches!(err, Error::Invalid(_)), "{}", err);
+        Ok(())
+    }
+
+    /// A device that, like one opened with `O_DIRECT`, only allows I/O of
+    /// whole, aligned blocks, to and from aligned buffers.
+    struct Direct {
+        inner: io::Cursor<StdVec<u8>>,
+        block: usize,
+    }
+
+    impl Direct {
+        fn check(&self, buf: &[u8]) -> io::Result<()> {
+            let pos = self.inner.position() as usize;
+            if !(buf.as_ptr() as usize).is_multiple_of(self.block)
+                || !pos.is_multiple_of(self.block)
+                || !buf.len().is_multiple_of(self.block)
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Unaligned direct I/O",
+                ));
+            }
+            Ok(())
+        }
+    }
+
+    impl Read for Direct {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.check(buf)?;
+            self.inner.read(buf)
+        }
+    }
+
+    impl Write for Direct {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.check(buf)?;
+            self.inner.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Seek for Direct {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    /// Test that Gpts can be used directly on `O_DIRECT` devices
+    #[test]
+    fn direct_io() -> Result {
+        for &size in &[512, 4096] {
+            let block_size = BlockSize::new(size);
+            let mut dev = Direct {
+                inner: io::Cursor::new(vec![0; TEN_MIB_BYTES]),
+                block: size as usize,
+            };
+            let mut gpt: Gpt = Gpt::new(Uuid::new_v4(), Size::from_mib(10), block_size);
+            let part = PartitionBuilder::new(Uuid::new_v4(), &gpt)
+                .start(gpt.first_usable())
+                .size(Size::from_mib(1))
+                .partition_type(PartitionType::LinuxFilesystemData)
+                .finish();
+            gpt.add_partition(part)?;
+            gpt.to_writer(&mut dev)?;
+            assert_eq!(Gpt::from_reader(&mut dev, block_size)?, gpt);
+            //
+            gpt.modify_partition(0, |p| p.set_name("Direct"))?;
+            gpt.to_writer_incremental(&mut dev)?;
+            assert_eq!(Gpt::from_reader(&mut dev, block_size)?, gpt);
+            assert_eq!(hybrid::from_reader::<StdVec<_>, _>(&mut dev)?, gpt);
+        }
+        Ok(())
+    }
+
+    /// Test that removed partitions don't leave stale entries behind
+    #[test]
+    fn zero_removed() -> Result {
+        let (mut gpt, mut raw) = written_gpt()?;
+        let backup_array = TEN_MIB_BYTES - 512 - (32 * 512);
+        assert_ne!(&raw[1024 + 128..][..128], &[0; 128][..]);
+        gpt.remove_partition(1);
+        gpt.to_bytes(&mut raw)?;
+        for &array in &[1024, backup_array] {
+            assert_eq!(&raw[array + 128..][..128], &[0; 128][..]);
+        }
+        // Both headers have the usual 128 entries
+        for &header in &[512, TEN_MIB_BYTES - 512] {
+            assert_eq!(&raw[header + 80..][..4], &128u32.to_le_bytes()[..]);
+        }
+        assert_eq!(Gpt::from_bytes(&raw, BLOCK_SIZE)?, gpt);
+        Ok(())
+    }
+
+    /// Test that only changed entries and headers are written
+    #[test]
+    fn incremental() -> Result {
+        let (mut gpt, raw) = written_gpt()?;
+        gpt.modify_partition(1, |p| p.set_name("Changed"))?;
+        let mut expected = raw.clone();
+        gpt.to_bytes(&mut expected)?;
+        //
+        let raw = core::cell::RefCell::new(raw);
+        let mut written = StdVec::new();
+        gpt.to_bytes_incremental_with_func(
+            |i, buf| {
+                buf.copy_from_slice(&raw.borrow()[i.0 as usize..][..buf.len()]);
+                Ok(())
+            },
+            |i, buf| {
+                raw.borrow_mut()[i.0 as usize..][..buf.len()].copy_from_slice(buf);
+                written.push(i.0 as usize);
+                Ok(())
+            },
+            || Ok(()),
+            BLOCK_SIZE,
+            Size::from_mib(10),
+        )?;
+        let last = TEN_MIB_BYTES - 512;
+        let backup_array = last - (32 * 512);
+        // The blocks with the changed entry, and the headers
+        assert_eq!(written, [backup_array, last, 1024, 512]);
+        assert!(raw.into_inner() == expected);
+        // Nothing changed, nothing written
+        let mut raw = expected;
+        gpt.to_bytes_incremental_with_func(
+            |i, buf| {
+                buf.copy_from_slice(&raw[i.0 as usize..][..buf.len()]);
+                Ok(())
+            },
+            |i, _| panic!("Unchanged write at {}", i),
+            || Ok(()),
+            BLOCK_SIZE,
+            Size::from_mib(10),
+        )?;
+        // And through a writer
+        gpt.modify_partition(0, |p| p.set_name("Also changed"))?;
+        let mut dest = io::Cursor::new(&mut raw[..]);
+        gpt.to_writer_incremental(&mut dest)?;
+        assert_eq!(Gpt::from_bytes(&raw, BLOCK_SIZE)?, gpt);
+        Ok(())
+    }
+
+    /// Test that a Gpt round trips through Serde, and the format is as
+    /// documented
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_round_trip() -> Result {
+        let mut gpt: Gpt = Gpt::new(Uuid::from_u128(1), Size::from_mib(10), BLOCK_SIZE);
+        let part = PartitionBuilder::new(Uuid::from_u128(2), &gpt)
+            .start(Block(2048))
+            .size(Size::from_mib(1))
+            .partition_type(PartitionType::LinuxFilesystemData)
+            .name("Root")
+            .attributes(1)
+            .finish();
+        gpt.add_partition(part)?;
+        //
+        let json = serde_json::to_value(&gpt)?;
+        assert_eq!(json["block_size"], 512);
+        assert_eq!(json["disk_size"], TEN_MIB_BYTES);
+        assert_eq!(json["first_usable"], 34);
+        assert_eq!(json["last_usable"], 20446);
+        assert_eq!(json["slots"], 128);
+        assert_eq!(
+            json["partitions"][0]["uuid"],
+            Uuid::from_u128(2).to_string()
+        );
+        assert_eq!(json["partitions"][0]["name"], "Root");
+        assert_eq!(json["partitions"][0]["attributes"], 1);
+        //
+        let de: Gpt = serde_json::from_value(json.clone())?;
+        assert_eq!(de, gpt);
+        let de: Gpt<Array<[Partition; 4]>> = serde_json::from_value(json)?;
+        assert_eq!(de.partitions(), gpt.partitions());
+        // A partition past the usual 128 slots
+        let mut json = serde_json::to_value(&gpt)?;
+        json["slots"] = 256.into();
+        json["partitions"][0]["slot"] = 200.into();
+        let high: Gpt = serde_json::from_value(json)?;
+        assert_eq!(high.partitions()[0].slot(), Some(200));
+        let json = serde_json::to_value(&high)?;
+        assert_eq!(json["slots"], 201);
+        assert_eq!(json["partitions"][0]["slot"], 200);
+        let de: Gpt = serde_json::from_value(json)?;
+        assert_eq!(de, high);
+        Ok(())
+    }
+
+    /// Test that deserializing validates the Gpt
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_validate() -> Result {
+        let gpt = |parts: &str, extra: &str| {
+            serde_json::from_str::<Gpt>(&format!(
+                r#"{{
+                    "uuid": "00000000-0000-0000-0000-000000000001",
+                    "block_size": 512,
+                    "disk_size": 10485760,
+                    {}
+                    "partitions": [{}]
+                }}"#,
+                extra, parts
+            ))
+        };
+        let part = |uuid: u8, start: u64, end: u64, name: &str| {
+            format!(
+                r#"{{
+                    "partition_type": "LinuxSwap",
+                    "uuid": "00000000-0000-0000-0000-0000000000{:02x}",
+                    "start": {},
+                    "end": {},
+                    "name": "{}"
+                }}"#,
+                uuid, start, end, name
+            )
+        };
+        // Optional fields, unsorted partitions
+        let de = gpt(
+            &format!("{}, {}", part(3, 4096, 4096, ""), part(2, 2048, 2048, "")),
+            "",
+        )?;
+        assert_eq!(de.partitions()[0].start(), Block(2048));
+        assert_eq!(de.partitions()[0].attributes(), 0);
+        // Invalid
+        let overlap = format!("{}, {}", part(2, 2048, 4096, ""), part(3, 4096, 4096, ""));
+        let duplicate = format!("{}, {}", part(2, 2048, 2048, ""), part(2, 4096, 4096, ""));
+        let long = "a".repeat(71);
+        for (parts, extra) in &[
+            (overlap, ""),
+            (duplicate, ""),
+            (part(2, 4096, 2048, ""), ""),
+            (part(2, 2048, 2048, &long), ""),
+            (part(2, 20447, 20447, ""), ""),
+            (part(2, 2048, 2048, ""), r#""first_usable": 2048,"#),
+            (part(2, 2048, 2048, ""), r#""slots": 64,"#),
+            (
+                part(2, 2048, 2048, "").replace("{", r#"{"slot": 129,"#),
+                r#""slots": 129,"#,
+            ),
+        ] {
+            assert!(
+                gpt(parts, extra).is_err(),
+                "Invalid Gpt was accepted: {} {}",
+                parts,
+                extra
+            );
+        }
+        // Not enough room in the container
+        let two = format!("{}, {}", part(2, 2048, 2048, ""), part(3, 4096, 4096, ""));
+        let json = format!(
+            r#"{{"uuid": "00000000-0000-0000-0000-000000000001", "block_size": 512, "disk_size": 10485760, "partitions": [{}]}}"#,
+            two
+        );
+        assert!(serde_json::from_str::<Gpt<Array<[Partition; 1]>>>(&json).is_err());
+        Ok(())
+    }
 }
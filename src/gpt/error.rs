@@ -21,11 +21,39 @@ pub enum Error {
     NotEnough,
 
     /// The GPT Header was invalid: {0}
+    ///
+    /// The table, or the device it's on, can't be used as a whole,
+    /// such as a bad CRC or a block size mismatch.
     Invalid(&'static str),
 
     /// Attempted to add overlapping partitions, or partition was out of usable
     /// range
     Overlap,
+
+    /// The Partition was invalid: {0}
+    ///
+    /// A single partition given by the caller was rejected, such as a
+    /// duplicate GUID. Unlike [`Error::Invalid`] the table itself is fine,
+    /// and is left unchanged.
+    InvalidPartition(&'static str),
+
+    /// The data does not fit in the partition
+    TooLarge,
+
+    /// Not enough free space on the disk
+    NoSpace,
+
+    /// The device is read only
+    ReadOnly,
+
+    /// Parse error on line {line}: {msg}
+    Parse {
+        /// Line number, starting at 1
+        line: usize,
+
+        /// What went wrong
+        msg: &'static str,
+    },
 }
 
 pub type Result<T> = core::result::Result<T, Error>;
@@ -9,7 +9,7 @@ use uuid::Uuid;
 ///
 /// With current GPT Partition entry sizes this means a minimum of 128
 /// partitions
-const MIN_PARTITIONS_BYTES: Size = Size::from_bytes(16384);
+pub const MIN_PARTITIONS_BYTES: Size = Size::from_bytes(16384);
 
 /// "EFI PART" constant as a u64
 const EFI_PART: u64 = 0x5452_4150_2049_4645;
@@ -27,6 +27,10 @@ pub const HEADER_SIZE: u32 = 92;
 /// Only used when writing, can in theory read and validate larger entries.
 pub const PARTITION_ENTRY_SIZE: u32 = 128;
 
+/// Number of entries in the minimum sized partition array.
+pub const PARTITION_SLOTS: u32 =
+    (MIN_PARTITIONS_BYTES.as_bytes() / PARTITION_ENTRY_SIZE as u64) as u32;
+
 /// GPT stores UUID's in big endian, but with the time* fields as little endian.
 ///
 /// See Appendix A for more details.
@@ -233,7 +237,7 @@ impl Header {
     /// # Errors
     ///
     /// - The GPT is invalid.
-    /// - [`Error::NotEnough`] if `source` is not `block_size` bytes
+    /// - [`Error::NotEnough`] if `source` is smaller than the header size.
     pub fn from_bytes(source: &[u8], block_size: BlockSize) -> Result<Self> {
         if source.len() < mem::size_of::<RawHeader>() {
             return Err(Error::NotEnough);
@@ -253,6 +257,9 @@ impl Header {
                 "Header size invalid, less than 92 or bigger than the block size",
             ));
         }
+        if raw.header_size as usize > source.len() {
+            return Err(Error::NotEnough);
+        }
         if raw.header_crc32 != calculate_crc(raw, &source[HEADER_SIZE as usize..]) {
             return Err(Error::Invalid("CRC mismatch"));
         }
@@ -2,9 +2,14 @@
 use super::{
     error::*,
     header::{uuid_hack, PARTITION_ENTRY_SIZE},
+    read_chunks,
+    GptC,
+    GptHelper,
 };
 use crate::{partitions::PartitionType, types::*};
 use arrayvec::ArrayString;
+#[cfg(feature = "serde")]
+use core::convert::TryFrom;
 use core::{
     char::{decode_utf16, REPLACEMENT_CHARACTER},
     fmt,
@@ -12,14 +17,18 @@ use core::{
     slice,
 };
 use crc::{crc32, Hasher32};
+#[cfg(feature = "serde")]
+use serde_crate::{Deserialize, Serialize};
 use uuid::Uuid;
 
 /// Calculate partition crc32.
 ///
 /// See [`super::Gpt::from_bytes`] for details.
 ///
+/// The array is read in whole blocks, see [`super::CHUNK_SIZE`].
+///
 /// `CB` receives the filled partition buffer, which is always
-/// PARTITION_ENTRY_SIZE bytes
+/// PARTITION_ENTRY_SIZE bytes, even if `entry_size` is larger.
 ///
 /// `CB` also receives the partition number, starting at zero.
 pub fn calculate_part_crc<F, CB>(
@@ -27,6 +36,7 @@ pub fn calculate_part_crc<F, CB>(
     partitions: u64,
     array_start: Offset,
     entry_size: usize,
+    block_size: BlockSize,
     cb: &mut CB,
 ) -> Result<u32>
 where
@@ -34,13 +44,33 @@ where
     CB: FnMut(usize, &[u8]) -> Result<()>,
 {
     let mut digest = crc32::Digest::new(crc32::IEEE);
-    let mut buf = [0; PARTITION_ENTRY_SIZE as usize];
-    for i in 0..partitions {
-        let b = Offset(array_start.0 + ((entry_size as u64) * i));
-        func(b, &mut buf)?;
-        cb(i as usize, &buf)?;
-        digest.write(&buf);
-    }
+    let mut entry = [0; PARTITION_ENTRY_SIZE as usize];
+    let array_len = partitions
+        .checked_mul(entry_size as u64)
+        .ok_or(Error::Invalid("Partition array too large"))?;
+    let blocks_len = array_len.div_ceil(block_size.get()) * block_size.get();
+    // Bytes of the array seen so far
+    let mut pos = 0;
+    read_chunks(func, array_start, blocks_len, block_size, |chunk| {
+        // Ignore the padding after the array
+        let len = (array_len - pos).min(chunk.len() as u64) as usize;
+        let mut chunk = &chunk[..len];
+        digest.write(chunk);
+        while !chunk.is_empty() {
+            let within = (pos % entry_size as u64) as usize;
+            let len = (entry_size - within).min(chunk.len());
+            if within < entry.len() {
+                let copy = len.min(entry.len() - within);
+                entry[within..][..copy].copy_from_slice(&chunk[..copy]);
+            }
+            pos += len as u64;
+            chunk = &chunk[len..];
+            if pos % entry_size as u64 == 0 {
+                cb((pos / entry_size as u64 - 1) as usize, &entry)?;
+            }
+        }
+        Ok(())
+    })?;
     Ok(digest.sum32())
 }
 
@@ -86,11 +116,17 @@ impl Default for RawPartition {
 ///
 /// TODO: List all partitions on a device
 #[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", try_from = "PartitionRepr")
+)]
 pub struct Partition {
     /// Defines the type of this partition
     partition_type: PartitionType,
 
     /// Unique identifer for this partition
+    #[cfg_attr(feature = "serde", serde(rename = "uuid"))]
     guid: Uuid,
 
     /// Where it starts on disk
@@ -109,6 +145,10 @@ pub struct Partition {
     ///
     /// Use size of 72 because it has a default impl and 70 doesn't.
     name: ArrayString<[u8; 72]>,
+
+    /// Slot in the partition array, if in a [`crate::GptC`].
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    slot: Option<usize>,
 }
 
 impl Partition {
@@ -120,9 +160,25 @@ impl Partition {
             end: Default::default(),
             attributes: Default::default(),
             name: Default::default(),
+            slot: None,
         }
     }
 
+    /// Set the slot in the partition array.
+    ///
+    /// The caller is responsible for validating it.
+    pub(crate) fn set_slot(&mut self, slot: Option<usize>) {
+        self.slot = slot;
+    }
+
+    /// Set the partition [`Block`] range, inclusive.
+    ///
+    /// The caller is responsible for validating it.
+    pub(crate) fn set_range(&mut self, start: Block, end: Block) {
+        self.start = start;
+        self.end = end;
+    }
+
     /// Read from bytes.
     ///
     /// Invalid characters in the partition name are replaced.
@@ -156,6 +212,7 @@ impl Partition {
             end: Block(part.ending_lba),
             attributes: part.attributes,
             name,
+            slot: None,
         })
     }
 
@@ -215,6 +272,96 @@ impl Partition {
     pub fn end(&self) -> Block {
         self.end
     }
+
+    /// Partition attribute bits
+    pub fn attributes(&self) -> u64 {
+        self.attributes
+    }
+
+    /// Slot of this partition in the partition array, starting at zero,
+    /// or `None` if it hasn't been added to a [`GptC`] yet.
+    ///
+    /// This is the partition number used by the kernel minus one,
+    /// so the partition in slot `2` is `sda3`.
+    /// Slots may be unused, so this is not always the index in
+    /// [`GptC::partitions`].
+    ///
+    /// [`GptC`]: crate::GptC
+    /// [`GptC::partitions`]: crate::GptC::partitions
+    pub fn slot(&self) -> Option<usize> {
+        self.slot
+    }
+
+    /// Set the partition name.
+    ///
+    /// # Panics
+    ///
+    /// - If name is more than 70 bytes.
+    pub fn set_name(&mut self, name: &str) {
+        assert!(name.len() <= 70);
+        self.name.clear();
+        self.name.push_str(name);
+    }
+
+    /// Set the partition type
+    pub fn set_partition_type(&mut self, p_type: PartitionType) {
+        self.partition_type = p_type;
+    }
+
+    /// Set the unique identifer for this Partition
+    ///
+    /// WARNING: Partition UUID's MUST be unique.
+    pub fn set_uuid(&mut self, uuid: Uuid) {
+        self.guid = uuid;
+    }
+
+    /// Set the partition attribute bits
+    pub fn set_attributes(&mut self, attributes: u64) {
+        self.attributes = attributes;
+    }
+}
+
+/// Unvalidated [`Partition`], for deserializing.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(crate = "serde_crate", rename = "Partition")]
+struct PartitionRepr {
+    partition_type: PartitionType,
+    uuid: Uuid,
+    start: Block,
+    end: Block,
+    #[serde(default)]
+    attributes: u64,
+    #[serde(default)]
+    name: ArrayString<[u8; 72]>,
+    #[serde(default)]
+    slot: Option<usize>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<PartitionRepr> for Partition {
+    type Error = Error;
+
+    fn try_from(repr: PartitionRepr) -> Result<Self> {
+        if repr.partition_type == PartitionType::Unused {
+            return Err(Error::InvalidPartition("Partition type is unused"));
+        }
+        if repr.end < repr.start {
+            return Err(Error::InvalidPartition("Partition ends before it starts"));
+        }
+        if repr.name.len() > 70 {
+            return Err(Error::InvalidPartition("Partition name too long"));
+        }
+        Ok(Self {
+            partition_type: repr.partition_type,
+            guid: repr.uuid,
+            start: repr.start,
+            end: repr.end,
+            attributes: repr.attributes,
+            name: repr.name,
+            slot: repr.slot,
+        })
+    }
 }
 
 impl fmt::Debug for Partition {
@@ -226,6 +373,7 @@ impl fmt::Debug for Partition {
             .field("end", &self.end)
             .field("attributes", &self.attributes)
             .field("name", &self.name())
+            .field("slot", &self.slot)
             .finish()
     }
 }
@@ -251,6 +399,8 @@ pub struct PartitionBuilder {
     partition_type: PartitionType,
     uuid: Uuid,
     name: ArrayString<[u8; 72]>,
+    attributes: u64,
+    slot: Option<usize>,
     block_size: BlockSize,
 }
 
@@ -265,10 +415,36 @@ impl PartitionBuilder {
             partition_type: Default::default(),
             uuid,
             name: Default::default(),
+            attributes: 0,
+            slot: None,
             block_size: gpt.block_size,
         }
     }
 
+    /// New builder, starting from a copy of `part`.
+    ///
+    /// All fields, including the unique identifer, are copied,
+    /// except the slot.
+    /// Use [`PartitionBuilder::uuid`] to give the new partition its own.
+    pub fn from_partition<C: GptHelper<C>>(part: &Partition, gpt: &GptC<C>) -> Self {
+        Self {
+            start: part.start,
+            end: End::Abs(part.end),
+            partition_type: part.partition_type,
+            uuid: part.guid,
+            name: part.name,
+            attributes: part.attributes,
+            slot: None,
+            block_size: gpt.block_size,
+        }
+    }
+
+    /// Partition unique identifer.
+    pub fn uuid(mut self, uuid: Uuid) -> Self {
+        self.uuid = uuid;
+        self
+    }
+
     /// Partition start. Required.
     pub fn start(mut self, start: Block) -> Self {
         self.start = start;
@@ -314,6 +490,22 @@ impl PartitionBuilder {
         self
     }
 
+    /// Partition attribute bits.
+    pub fn attributes(mut self, attributes: u64) -> Self {
+        self.attributes = attributes;
+        self
+    }
+
+    /// Slot in the partition array, starting at zero.
+    ///
+    /// By default [`GptC::add_partition`] uses the first unused slot.
+    ///
+    /// [`GptC::add_partition`]: crate::GptC::add_partition
+    pub fn slot(mut self, slot: usize) -> Self {
+        self.slot = Some(slot);
+        self
+    }
+
     /// Create Partition
     ///
     /// # Panics
@@ -340,8 +532,9 @@ impl PartitionBuilder {
             guid: self.uuid,
             start: self.start,
             end,
-            attributes: 0,
+            attributes: self.attributes,
             name: self.name,
+            slot: self.slot,
         }
     }
 }
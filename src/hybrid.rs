@@ -0,0 +1,286 @@
+//! GPTs on isohybrid images
+//!
+//! Hybrid ISO images, such as those made by `xorriso` or `isohybrid`, carry a
+//! GPT so they can also boot from a USB stick. These bend the usual rules:
+//!
+//! - The MBR is a hybrid, describing real partitions, rather than protective.
+//! - The GPT may use 2048 byte blocks, matching the ISO 9660 sector size.
+//! - The backup header is often not on the last block, because the image was
+//!   padded after the GPT was written.
+//!
+//! The readers here detect the block size and tolerate all of the above.
+//! Unlike [`GptC::from_bytes`], they can't recover from a damaged primary
+//! header. Without it there's no way to find the backup, so it's only used if
+//! it happens to be in the last block, as on an image that wasn't padded.
+//! Partition locations should be taken in bytes, from
+//! [`GptC::partition_offset`] and [`GptC::partition_size`], as the detected
+//! block size rarely matches the device the image ends up on.
+//!
+//! # Examples
+//!
+//! Find the EFI system image embedded in an ISO
+//!
+//! ```rust,no_run
+//! # use parts::{hybrid, Gpt, PartitionType};
+//! # #[cfg(feature = "std")]
+//! # use std::fs::File;
+//! # #[cfg(not(feature = "std"))]
+//! # fn main() {}
+//! # #[cfg(feature = "std")]
+//! # fn main() -> anyhow::Result<()> {
+//! let gpt: Gpt = hybrid::from_reader(File::open("distro.iso")?)?;
+//! if let Some((_, esp)) = gpt.find_by_type(PartitionType::EfiSystem).next() {
+//!     println!(
+//!         "mount -o loop,offset={},sizelimit={} distro.iso /mnt",
+//!         gpt.partition_offset(esp).0,
+//!         gpt.partition_size(esp).as_bytes(),
+//!     );
+//! }
+//! # Ok(()) }
+//! ```
+use crate::{
+    gpt::{error::*, header::Header, ChunkBuf, GptHelper, CHUNK_SIZE},
+    types::*,
+    GptC,
+};
+use core::convert::TryInto;
+#[cfg(feature = "std")]
+use std::io::{prelude::*, SeekFrom};
+
+/// Block sizes that are tried, in order.
+const BLOCK_SIZES: [u64; 3] = [512, 2048, 4096];
+
+/// Detect the block size of the GPT on a device, using `func`.
+///
+/// Each of 512, 2048 and 4096 is tried in turn, looking for a valid GPT
+/// header at LBA 1. If there is none, such as after an interrupted write,
+/// they are tried again looking for a backup header in the last block.
+/// On isohybrid images the backup is rarely there, see the
+/// [module docs](self).
+///
+/// `func` receives a byte offset into the device,
+/// and a buffer to read into.
+///
+/// # Errors
+///
+/// - [`Error::Invalid`] if there is no GPT.
+/// - If `func` does.
+pub fn detect_block_size<F: FnMut(Offset, &mut [u8]) -> Result<()>>(
+    mut func: F,
+    disk_size: Size,
+) -> Result<BlockSize> {
+    for &backup in &[false, true] {
+        for &size in &BLOCK_SIZES {
+            let block_size = BlockSize::new(size);
+            // Room for at least the MBR, header, and a backup header.
+            if disk_size < Size::from_bytes(size * 3) {
+                break;
+            }
+            let lba = if backup {
+                (disk_size / block_size) - 1
+            } else {
+                Block(1)
+            };
+            // Read the whole aligned chunk around the header,
+            // so reads are aligned whatever the real block size is.
+            let at = (lba * block_size).0;
+            let start = at - (at % CHUNK_SIZE as u64);
+            let (start, len) = if start + CHUNK_SIZE as u64 <= disk_size.as_bytes() {
+                (start, CHUNK_SIZE)
+            } else {
+                (at, size as usize)
+            };
+            let mut buf = ChunkBuf::new();
+            let buf = &mut buf.0[..len];
+            func(Offset(start), buf)?;
+            match Header::from_bytes(&buf[(at - start) as usize..], block_size) {
+                Ok(header) if header.this == lba => return Ok(block_size),
+                _ => continue,
+            }
+        }
+    }
+    Err(Error::Invalid("No GPT found"))
+}
+
+/// Read the Gpt from an isohybrid image using `func`.
+///
+/// The block size is detected with [`detect_block_size`], and the backup
+/// header is read from wherever the primary header says it is.
+/// It must still be within `disk_size`, and match the primary.
+///
+/// If the primary header is damaged the backup is only found, and used,
+/// if it's in the last block. See the [module docs](self).
+///
+/// `func` receives a byte offset into the device,
+/// and a buffer to read into.
+///
+/// # Errors
+///
+/// - [`Error::Invalid`] if the GPT is invalid, or there is none.
+/// - [`Error::NotEnough`] if the device is too small.
+/// - If `func` does.
+pub fn from_bytes_with_func<C, F>(mut func: F, disk_size: Size) -> Result<GptC<C>>
+where
+    C: GptHelper<C>,
+    F: FnMut(Offset, &mut [u8]) -> Result<()>,
+{
+    let block_size = detect_block_size(&mut func, disk_size)?;
+    GptC::read_with_func(func, block_size, disk_size, true).map(|(gpt, _)| gpt)
+}
+
+/// Read the Gpt from an isohybrid image in `source`.
+///
+/// See [`from_bytes_with_func`] for details.
+///
+/// # Errors
+///
+/// - [`Error::Invalid`] if the GPT is invalid, or there is none.
+/// - [`Error::NotEnough`] if `source` is too small.
+pub fn from_bytes<C: GptHelper<C>>(source: &[u8]) -> Result<GptC<C>> {
+    from_bytes_with_func(
+        |i, buf| {
+            let i = i.0 as usize;
+            let size = buf.len();
+            buf.copy_from_slice(
+                source
+                    .get(i..)
+                    .ok_or(Error::NotEnough)?
+                    .get(..size)
+                    .ok_or(Error::NotEnough)?,
+            );
+            Ok(())
+        },
+        Size::from_bytes(source.len().try_into().expect("Source too large for u64")),
+    )
+}
+
+/// Read the Gpt from an isohybrid image in `source`.
+///
+/// See [`from_bytes_with_func`] for details.
+///
+/// # Errors
+///
+/// - [`Error::Invalid`] if the GPT is invalid, or there is none.
+/// - [`Error::NotEnough`] if `source` is too small.
+/// - [`Error::Io`] if I/O does.
+#[cfg(feature = "std")]
+pub fn from_reader<C: GptHelper<C>, RS: Read + Seek>(mut source: RS) -> Result<GptC<C>> {
+    let disk_size = Size::from_bytes(source.seek(SeekFrom::End(0))?);
+    from_bytes_with_func(
+        |i, buf| {
+            source.seek(SeekFrom::Start(i.0))?;
+            source.read_exact(buf)?;
+            Ok(())
+        },
+        disk_size,
+    )
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{
+        util::{Result, TEN_MIB_BYTES},
+        Gpt,
+        PartitionBuilder,
+        PartitionType,
+    };
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+    use uuid::Uuid;
+
+    /// Offset of the first MBR partition entry.
+    const MBR_PART: usize = 446;
+
+    /// An isohybrid style image, GPT written for 5 MiB then padded to 10 MiB,
+    /// with a hybrid MBR.
+    fn iso(block_size: BlockSize) -> Result<(Gpt, Vec<u8>)> {
+        let mut gpt: Gpt = Gpt::new(Uuid::from_u128(1), Size::from_mib(5), block_size);
+        let iso = PartitionBuilder::new(Uuid::from_u128(2), &gpt)
+            .start(Size::from_kib(64) / block_size)
+            .size(Size::from_mib(2))
+            .partition_type(PartitionType::MicrosoftBasicData)
+            .name("ISO9660")
+            .finish();
+        gpt.add_partition(iso)?;
+        let esp = PartitionBuilder::new(Uuid::from_u128(3), &gpt)
+            .start(Size::from_mib(3) / block_size)
+            .size(Size::from_mib(1))
+            .partition_type(PartitionType::EfiSystem)
+            .name("Appended2")
+            .finish();
+        gpt.add_partition(esp)?;
+        let mut disk = vec![0; TEN_MIB_BYTES / 2];
+        gpt.to_bytes(&mut disk)?;
+        disk.resize(TEN_MIB_BYTES, 0);
+        // Hybrid MBR, a bootable partition and an EFI one.
+        let mbr = &mut disk[MBR_PART..][..64];
+        mbr.iter_mut().for_each(|b| *b = 0);
+        mbr[0] = 0x80;
+        mbr[4] = 0x00;
+        mbr[16 + 4] = 0xEF;
+        Ok((gpt, disk))
+    }
+
+    #[test]
+    fn isohybrid() -> Result {
+        for &size in &[512, 2048] {
+            let block_size = BlockSize::new(size);
+            let (gpt, disk) = iso(block_size)?;
+            // The strict reader refuses it
+            assert!(<Gpt>::from_bytes(&disk, block_size).is_err());
+            //
+            let hybrid: Gpt = from_reader(Cursor::new(&disk))?;
+            assert_eq!(hybrid.block_size(), block_size);
+            assert_eq!(hybrid.uuid(), gpt.uuid());
+            assert_eq!(hybrid.partitions(), gpt.partitions());
+            assert_eq!(hybrid.disk_size(), Size::from_mib(10));
+            let (_, esp) = hybrid
+                .find_by_type(PartitionType::EfiSystem)
+                .next()
+                .expect("Missing ESP");
+            assert_eq!(hybrid.partition_offset(esp), Offset(3 * 1024 * 1024));
+            assert_eq!(hybrid.partition_size(esp), Size::from_mib(1));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn detect() -> Result {
+        for &size in &BLOCK_SIZES {
+            let block_size = BlockSize::new(size);
+            let gpt: Gpt = Gpt::new(Uuid::from_u128(1), Size::from_mib(10), block_size);
+            let mut disk = vec![0; TEN_MIB_BYTES];
+            gpt.to_bytes(&mut disk)?;
+            let found: Gpt = from_bytes(&disk)?;
+            assert_eq!(found.block_size(), block_size);
+            // Normal GPTs still work
+            assert_eq!(found, Gpt::from_bytes(&disk, block_size)?);
+            // Only the backup header
+            disk[size as usize..][..size as usize].fill(0);
+            assert_eq!(from_bytes::<Vec<_>>(&disk)?, found);
+        }
+        assert!(from_bytes::<Vec<_>>(&[0; TEN_MIB_BYTES]).is_err());
+        Ok(())
+    }
+
+    /// Without the primary header the backup is only found if it's in the
+    /// last block
+    #[test]
+    fn damaged_primary() -> Result {
+        for &size in &[512, 2048] {
+            let block_size = BlockSize::new(size);
+            let (gpt, mut disk) = iso(block_size)?;
+            disk[size as usize..][..size as usize].fill(0);
+            // Padded, so the backup isn't in the last block
+            let err = from_bytes::<Vec<_>>(&disk).unwrap_err();
+            assert!(matches!(err, Error::Invalid(_)), "{}", err);
+            // Not padded
+            disk.truncate(TEN_MIB_BYTES / 2);
+            let found: Gpt = from_bytes(&disk)?;
+            assert_eq!(found.block_size(), block_size);
+            assert_eq!(found.partitions(), gpt.partitions());
+        }
+        Ok(())
+    }
+}
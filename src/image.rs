@@ -0,0 +1,334 @@
+//! Assemble complete disk images
+//!
+//! Describe the partitions you want, along with files containing their
+//! contents, and [`ImageBuilder`] will create a correctly sized image with a
+//! protective MBR, both GPT copies, and every partition's contents in place.
+use crate::{
+    gpt::error::*,
+    io::{import_partition, CopyOptions},
+    types::*,
+    Gpt,
+    PartitionBuilder,
+    PartitionType,
+};
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+use uuid::Uuid;
+
+/// Partitions are aligned to 1MiB.
+const ALIGN: u64 = 1024 * 1024;
+
+/// Round `bytes` up to a multiple of `to`
+fn align_up(bytes: u64, to: u64) -> u64 {
+    bytes.div_ceil(to) * to
+}
+
+#[derive(Debug, Clone, Copy)]
+enum PartSize {
+    None,
+    Fixed(Size),
+    FitContent,
+}
+
+/// A partition in an [`ImageBuilder`]
+#[derive(Debug, Clone)]
+pub struct ImagePartition {
+    partition_type: PartitionType,
+    uuid: Uuid,
+    name: String,
+    attributes: u64,
+    size: PartSize,
+    source: Option<PathBuf>,
+}
+
+impl ImagePartition {
+    /// New partition of type `p_type`, with a random unique identifer.
+    pub fn new(p_type: PartitionType) -> Self {
+        Self {
+            partition_type: p_type,
+            uuid: Uuid::new_v4(),
+            name: String::new(),
+            attributes: 0,
+            size: PartSize::None,
+            source: None,
+        }
+    }
+
+    /// Partition unique identifer.
+    pub fn uuid(mut self, uuid: Uuid) -> Self {
+        self.uuid = uuid;
+        self
+    }
+
+    /// Partition name.
+    ///
+    /// # Panics
+    ///
+    /// - If name is more than 70 bytes.
+    pub fn name(mut self, name: &str) -> Self {
+        assert!(name.len() <= 70);
+        self.name = name.into();
+        self
+    }
+
+    /// Partition attribute bits.
+    pub fn attributes(mut self, attributes: u64) -> Self {
+        self.attributes = attributes;
+        self
+    }
+
+    /// Partition size. Required, unless [`ImagePartition::fit_content`] is
+    /// used.
+    ///
+    /// Rounded **up** to the block size.
+    pub fn size(mut self, size: Size) -> Self {
+        self.size = PartSize::Fixed(size);
+        self
+    }
+
+    /// Size the partition to exactly fit its source file, rounded up to the
+    /// block size.
+    pub fn fit_content(mut self) -> Self {
+        self.size = PartSize::FitContent;
+        self
+    }
+
+    /// File to copy into the partition.
+    ///
+    /// Without one the partition is left empty.
+    pub fn source<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.source = Some(path.into());
+        self
+    }
+}
+
+/// Build a disk image.
+///
+/// Partitions are placed in order, each aligned to 1MiB.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use parts::{image::*, types::*, PartitionType};
+/// # fn main() -> anyhow::Result<()> {
+/// let gpt = ImageBuilder::new(BlockSize::new(512))
+///     .partition(
+///         ImagePartition::new(PartitionType::EfiSystem)
+///             .name("ESP")
+///             .source("esp.vfat")
+///             .fit_content(),
+///     )
+///     .partition(
+///         ImagePartition::new(PartitionType::LinuxFilesystemData)
+///             .name("root")
+///             .source("root.ext4")
+///             .size(Size::from_gib(2)),
+///     )
+///     .build("disk.img")?;
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ImageBuilder {
+    uuid: Uuid,
+    block_size: BlockSize,
+    disk_size: Option<Size>,
+    partitions: Vec<ImagePartition>,
+}
+
+impl ImageBuilder {
+    /// New builder, with a random disk unique identifer.
+    pub fn new(block_size: BlockSize) -> Self {
+        Self {
+            uuid: Uuid::new_v4(),
+            block_size,
+            disk_size: None,
+            partitions: Vec::new(),
+        }
+    }
+
+    /// Disk unique identifer.
+    pub fn uuid(mut self, uuid: Uuid) -> Self {
+        self.uuid = uuid;
+        self
+    }
+
+    /// Disk size.
+    ///
+    /// By default the disk is just large enough to fit all partitions,
+    /// rounded up to 1MiB.
+    pub fn disk_size(mut self, size: Size) -> Self {
+        self.disk_size = Some(size);
+        self
+    }
+
+    /// Add a partition after all previous ones.
+    pub fn partition(mut self, part: ImagePartition) -> Self {
+        self.partitions.push(part);
+        self
+    }
+
+    /// Calculate the [`Gpt`] for the image, without creating it.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::InvalidPartition`] if a partition has no size.
+    /// - [`Error::TooLarge`] if a source file doesn't fit in its partition.
+    /// - [`Error::Overlap`] if the partitions don't fit in the disk size.
+    /// - [`Error::Io`] if a source file can't be read.
+    pub fn layout(&self) -> Result<Gpt> {
+        let block_size = self.block_size.get();
+        let mut sizes = Vec::with_capacity(self.partitions.len());
+        for part in &self.partitions {
+            let content = match &part.source {
+                Some(path) => Some(fs::metadata(path)?.len()),
+                None => None,
+            };
+            let size = match (part.size, content) {
+                (PartSize::Fixed(size), Some(content)) if content > size.as_bytes() => {
+                    return Err(Error::TooLarge);
+                }
+                (PartSize::Fixed(size), _) => size.as_bytes(),
+                (PartSize::FitContent, Some(content)) => content,
+                (PartSize::FitContent, None) | (PartSize::None, _) => {
+                    return Err(Error::InvalidPartition("Partition has no size"));
+                }
+            };
+            if size == 0 {
+                return Err(Error::InvalidPartition("Partition has no size"));
+            }
+            sizes.push(Size::from_bytes(align_up(size, block_size)));
+        }
+        // Enough for every partition, 1MiB aligned, with 1MiB to spare at both
+        // ends for the GPT.
+        let disk_size = match self.disk_size {
+            Some(size) => size,
+            None => {
+                let parts: u64 = sizes.iter().map(|s| align_up(s.as_bytes(), ALIGN)).sum();
+                Size::from_bytes(parts + (2 * ALIGN))
+            }
+        };
+        let mut gpt: Gpt = Gpt::new(self.uuid, disk_size, self.block_size);
+        for (part, size) in self.partitions.iter().zip(sizes) {
+            let start = Offset(align_up((gpt.next_usable() * self.block_size).0, ALIGN));
+            let new = PartitionBuilder::new(part.uuid, &gpt)
+                .start(start / self.block_size)
+                .size(size)
+                .partition_type(part.partition_type)
+                .name(&part.name)
+                .attributes(part.attributes)
+                .finish();
+            if new.end() > gpt.last_usable() {
+                return Err(Error::Overlap);
+            }
+            gpt.add_partition(new)?;
+        }
+        Ok(gpt)
+    }
+
+    /// Create the image at `path`, overwriting anything already there.
+    ///
+    /// The image is created sparse, only the GPT and partition contents are
+    /// written.
+    ///
+    /// # Errors
+    ///
+    /// - See [`ImageBuilder::layout`]
+    /// - [`Error::Io`] if I/O does.
+    pub fn build<P: AsRef<Path>>(&self, path: P) -> Result<Gpt> {
+        let gpt = self.layout()?;
+        let mut image = File::create(path)?;
+        image.set_len(gpt.disk_size().as_bytes())?;
+        gpt.to_writer(&mut image)?;
+        for (part, new) in self.partitions.iter().zip(gpt.partitions()) {
+            if let Some(source) = &part.source {
+                let source = File::open(source)?;
+                let options = CopyOptions::new().sparse(true);
+                import_partition(&gpt, new, source, &mut image, options)?;
+            }
+        }
+        image.sync_all()?;
+        Ok(gpt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{Result, BLOCK_SIZE};
+    use std::env;
+
+    /// Temporary file path for `name`
+    fn temp(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("parts-image-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn build_image() -> Result {
+        let esp = temp("esp");
+        let root = temp("root");
+        let image = temp("image");
+        fs::write(&esp, b"ESP".repeat(1000))?;
+        fs::write(&root, b"Root".repeat(1000))?;
+        //
+        let built = ImageBuilder::new(BLOCK_SIZE)
+            .uuid(Uuid::from_u128(1))
+            .partition(
+                ImagePartition::new(PartitionType::EfiSystem)
+                    .uuid(Uuid::from_u128(2))
+                    .name("ESP")
+                    .source(&esp)
+                    .fit_content(),
+            )
+            .partition(
+                ImagePartition::new(PartitionType::LinuxFilesystemData)
+                    .name("root")
+                    .source(&root)
+                    .size(Size::from_mib(2)),
+            )
+            .build(&image)?;
+        let data = fs::read(&image)?;
+        for path in &[&esp, &root, &image] {
+            fs::remove_file(path)?;
+        }
+        //
+        let gpt: Gpt = Gpt::from_bytes(&data, BLOCK_SIZE)?;
+        assert_eq!(gpt, built);
+        assert_eq!(gpt.uuid(), Uuid::from_u128(1));
+        assert_eq!(gpt.disk_size(), Size::from_mib(5));
+        //
+        let esp = gpt.partitions()[0];
+        assert_eq!(esp.uuid(), Uuid::from_u128(2));
+        assert_eq!(esp.name(), "ESP");
+        assert_eq!(esp.start(), Block(2048));
+        // 3000 bytes rounds up to 6 blocks
+        assert_eq!(esp.end(), Block(2048 + 5));
+        assert_eq!(&data[1024 * 1024..][..3000], &b"ESP".repeat(1000)[..]);
+        //
+        let root = gpt.partitions()[1];
+        assert_eq!(root.start(), Block(4096));
+        assert_eq!(root.end(), Block(4096 + 4095));
+        assert_eq!(&data[2 * 1024 * 1024..][..4000], &b"Root".repeat(1000)[..]);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_layouts() {
+        let builder = ImageBuilder::new(BLOCK_SIZE)
+            .partition(ImagePartition::new(PartitionType::LinuxFilesystemData));
+        match builder.layout() {
+            Err(Error::InvalidPartition(_)) => (),
+            e => panic!("Partition without size was allowed: {:?}", e),
+        }
+        let builder = ImageBuilder::new(BLOCK_SIZE)
+            .disk_size(Size::from_mib(2))
+            .partition(
+                ImagePartition::new(PartitionType::LinuxFilesystemData).size(Size::from_mib(1)),
+            );
+        match builder.layout() {
+            Err(Error::Overlap) => (),
+            e => panic!("Partition larger than the disk was allowed: {:?}", e),
+        }
+    }
+}
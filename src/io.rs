@@ -0,0 +1,504 @@
+//! `std` I/O integration
+use crate::{
+    gpt::{error::*, partition::Partition, GptHelper},
+    types::*,
+    GptC,
+};
+use std::io::{self, prelude::*, SeekFrom};
+
+/// Size of the buffer used when copying partitions.
+const COPY_BUF: usize = 1024 * 1024;
+
+/// Options for [`import_partition`].
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CopyOptions {
+    zero_fill: bool,
+    sparse: bool,
+}
+
+impl CopyOptions {
+    /// New default options.
+    ///
+    /// By default everything is copied, and nothing is zero filled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// When importing, fill the rest of the partition after the file with
+    /// zeros.
+    pub fn zero_fill(mut self, zero_fill: bool) -> Self {
+        self.zero_fill = zero_fill;
+        self
+    }
+
+    /// Don't write blocks of all zeros, seek over them instead.
+    ///
+    /// Skipped blocks are left as-is in the destination,
+    /// so this should only be used when the partition is known to already be
+    /// zeroed, such as in a freshly created image.
+    ///
+    /// See [`extract_partition_sparse`] to extract to a sparse file.
+    pub fn sparse(mut self, sparse: bool) -> Self {
+        self.sparse = sparse;
+        self
+    }
+}
+
+/// Copy the contents of `part`, from `gpt`, on `source`, to `dest`.
+///
+/// Returns the number of bytes copied, which is always the partition size.
+///
+/// # Errors
+///
+/// - [`Error::Io`] if I/O does, or `source` ends before the partition does.
+pub fn extract_partition<C, RS, W>(
+    gpt: &GptC<C>,
+    part: &Partition,
+    source: RS,
+    mut dest: W,
+) -> Result<Size>
+where
+    C: GptHelper<C>,
+    RS: Read + Seek,
+    W: Write,
+{
+    let size = extract(gpt, part, source, |buf| dest.write_all(buf))?;
+    dest.flush()?;
+    Ok(size)
+}
+
+/// Copy the contents of `part`, from `gpt`, on `source`, to `dest`,
+/// seeking over blocks of all zeros rather than writing them.
+///
+/// This creates a sparse file, which is why `dest` must be [`Seek`].
+///
+/// See [`extract_partition`] for details.
+///
+/// # Errors
+///
+/// - [`Error::Io`] if I/O does, or `source` ends before the partition does.
+pub fn extract_partition_sparse<C, RS, WS>(
+    gpt: &GptC<C>,
+    part: &Partition,
+    source: RS,
+    mut dest: WS,
+) -> Result<Size>
+where
+    C: GptHelper<C>,
+    RS: Read + Seek,
+    WS: Write + Seek,
+{
+    let mut skipped = false;
+    let size = extract(gpt, part, source, |buf| {
+        skipped = write_chunk(&mut dest, buf, true)?;
+        Ok(())
+    })?;
+    if skipped {
+        // Make sure the file is the correct length when it ends in a hole.
+        dest.seek(SeekFrom::Current(-1))?;
+        dest.write_all(&[0])?;
+    }
+    dest.flush()?;
+    Ok(size)
+}
+
+/// Read all of `part`, from `gpt`, on `source`, passing each chunk to
+/// `write`.
+///
+/// # Errors
+///
+/// - [`Error::Io`] if I/O does, or `source` ends before the partition does.
+fn extract<C, RS, F>(gpt: &GptC<C>, part: &Partition, source: RS, mut write: F) -> Result<Size>
+where
+    C: GptHelper<C>,
+    RS: Read + Seek,
+    F: FnMut(&[u8]) -> io::Result<()>,
+{
+    let mut source = PartitionIo::new(gpt, part, source);
+    let size = source.size();
+    let mut buf = vec![0; COPY_BUF];
+    let mut copied = 0;
+    loop {
+        let read = read_full(&mut source, &mut buf)?;
+        if read == 0 {
+            break;
+        }
+        write(&buf[..read])?;
+        copied += read as u64;
+    }
+    if copied != size.as_bytes() {
+        return Err(Error::Io(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Source ended before the end of the partition",
+        )));
+    }
+    Ok(size)
+}
+
+/// Copy all of `source` into `part`, from `gpt`, on `dest`.
+///
+/// Returns the number of bytes copied from `source`.
+///
+/// # Errors
+///
+/// - [`Error::TooLarge`] if `source` doesn't fit in the partition. Nothing will
+///   have been written.
+/// - [`Error::Io`] if I/O does.
+pub fn import_partition<C, RS, WS>(
+    gpt: &GptC<C>,
+    part: &Partition,
+    mut source: RS,
+    dest: WS,
+    options: CopyOptions,
+) -> Result<Size>
+where
+    C: GptHelper<C>,
+    RS: Read + Seek,
+    WS: Write + Seek,
+{
+    let mut dest = PartitionIo::new(gpt, part, dest);
+    let len = source.seek(SeekFrom::End(0))?;
+    source.seek(SeekFrom::Start(0))?;
+    if len > dest.size().as_bytes() {
+        return Err(Error::TooLarge);
+    }
+    let mut buf = vec![0; COPY_BUF];
+    let mut copied = 0;
+    loop {
+        let read = read_full(&mut source, &mut buf)?;
+        if read == 0 {
+            break;
+        }
+        write_chunk(&mut dest, &buf[..read], options.sparse)?;
+        copied += read as u64;
+    }
+    if copied != len {
+        return Err(Error::Io(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Source changed size while importing",
+        )));
+    }
+    if options.zero_fill {
+        let zeros = vec![0; COPY_BUF];
+        loop {
+            let len = (zeros.len() as u64).min(dest.remaining()) as usize;
+            if len == 0 {
+                break;
+            }
+            dest.write_all(&zeros[..len])?;
+        }
+    }
+    dest.flush()?;
+    Ok(Size::from_bytes(copied))
+}
+
+/// Fill as much of `buf` as possible, stopping only at EOF.
+fn read_full<R: Read>(source: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match source.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read)
+}
+
+/// Write `buf` to `dest`, or seek over it if `sparse` and it's all zeros.
+///
+/// Returns whether the chunk was skipped.
+fn write_chunk<W: Write + Seek>(dest: &mut W, buf: &[u8], sparse: bool) -> io::Result<bool> {
+    if sparse && buf.iter().all(|b| *b == 0) {
+        dest.seek(SeekFrom::Current(buf.len() as i64))?;
+        Ok(true)
+    } else {
+        dest.write_all(buf)?;
+        Ok(false)
+    }
+}
+
+/// A [`Read`]/[`Write`]/[`Seek`] view of a single [`Partition`]'s contents.
+///
+/// All offsets are relative to the start of the partition,
+/// and all I/O is confined to the partition's bytes.
+///
+/// Reads stop at the end of the partition.
+/// Seeking outside the partition, or writing past the end of it, is an error.
+///
+/// # Examples
+///
+/// Read the first block of the first partition
+///
+/// ```rust,no_run
+/// # use parts::{Gpt, io::PartitionIo, types::*};
+/// # use std::{fs::File, io::prelude::*};
+/// # fn main() -> anyhow::Result<()> {
+/// let mut disk = File::open("tests/data/test_parts_cf")?;
+/// let gpt: Gpt = Gpt::from_reader(&mut disk, BlockSize::new(512))?;
+/// let mut part = PartitionIo::new(&gpt, &gpt.partitions()[0], &mut disk);
+/// let mut block = [0; 512];
+/// part.read_exact(&mut block)?;
+/// # Ok(()) }
+/// ```
+#[derive(Debug)]
+pub struct PartitionIo<T> {
+    inner: T,
+
+    /// Byte offset of the partition on the device
+    start: Offset,
+
+    /// Byte size of the partition
+    size: Size,
+
+    /// Current position, relative to `start`
+    pos: u64,
+}
+
+impl<T> PartitionIo<T> {
+    /// Create a view of `part`, from `gpt`, on `inner`.
+    ///
+    /// `inner` should be the device `gpt` was read from.
+    pub fn new<C: GptHelper<C>>(gpt: &GptC<C>, part: &Partition, inner: T) -> Self {
+        Self {
+            inner,
+            start: gpt.partition_offset(part),
+            size: gpt.partition_size(part),
+            pos: 0,
+        }
+    }
+
+    /// Byte [`Offset`] of the partition on the underlying device
+    pub fn offset(&self) -> Offset {
+        self.start
+    }
+
+    /// [`Size`] of the partition
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    /// Return the underlying device
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Bytes left between the current position and the end of the partition
+    fn remaining(&self) -> u64 {
+        self.size.as_bytes().saturating_sub(self.pos)
+    }
+}
+
+impl<T: Seek> PartitionIo<T> {
+    /// Seek `inner` to our current position.
+    fn seek_inner(&mut self) -> io::Result<()> {
+        self.inner.seek(SeekFrom::Start(self.start.0 + self.pos))?;
+        Ok(())
+    }
+}
+
+impl<T: Read + Seek> Read for PartitionIo<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = (buf.len() as u64).min(self.remaining()) as usize;
+        if len == 0 {
+            return Ok(0);
+        }
+        self.seek_inner()?;
+        let read = self.inner.read(&mut buf[..len])?;
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+impl<T: Write + Seek> Write for PartitionIo<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let len = (buf.len() as u64).min(self.remaining()) as usize;
+        if len == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::WriteZero,
+                "Attempted to write past the end of the partition",
+            ));
+        }
+        self.seek_inner()?;
+        let written = self.inner.write(&buf[..len])?;
+        self.pos += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<T: Seek> Seek for PartitionIo<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let size = self.size.as_bytes();
+        let new = match pos {
+            SeekFrom::Start(p) => Some(p),
+            SeekFrom::End(p) => offset_by(size, p),
+            SeekFrom::Current(p) => offset_by(self.pos, p),
+        };
+        match new {
+            Some(new) if new <= size => {
+                self.pos = new;
+                Ok(new)
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Attempted to seek outside the partition",
+            )),
+        }
+    }
+}
+
+/// `base + offset`, or `None` on over/underflow.
+fn offset_by(base: u64, offset: i64) -> Option<u64> {
+    if offset < 0 {
+        base.checked_sub(offset.unsigned_abs())
+    } else {
+        base.checked_add(offset as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        util::{Result, *},
+        Gpt,
+        PartitionBuilder,
+        PartitionType,
+    };
+    use std::{fs, io::Cursor};
+    use uuid::Uuid;
+
+    /// 10MiB disk with a 1MiB partition at 1MiB
+    fn disk() -> Result<(Gpt, Cursor<Vec<u8>>)> {
+        let mut disk = Cursor::new(vec![0; TEN_MIB_BYTES]);
+        let mut gpt: Gpt = Gpt::new(Uuid::new_v4(), Size::from_mib(10), BLOCK_SIZE);
+        let part = PartitionBuilder::new(Uuid::new_v4(), &gpt)
+            .start(Size::from_mib(1) / BLOCK_SIZE)
+            .size(Size::from_mib(1))
+            .partition_type(PartitionType::LinuxFilesystemData)
+            .finish();
+        gpt.add_partition(part)?;
+        gpt.to_writer(&mut disk)?;
+        Ok((gpt, disk))
+    }
+
+    /// I/O goes to the right place, and stays within the partition
+    #[test]
+    fn bounded_io() -> Result {
+        let (gpt, mut disk) = disk()?;
+        let mut part = PartitionIo::new(&gpt, &gpt.partitions()[0], &mut disk);
+        assert_eq!(part.offset(), Offset(1024 * 1024));
+        assert_eq!(part.size(), Size::from_mib(1));
+        //
+        part.write_all(b"Start")?;
+        part.seek(SeekFrom::End(-3))?;
+        part.write_all(b"End")?;
+        assert!(part.write_all(b"!").is_err(), "Wrote past the end");
+        // Only the part that fits is written
+        part.seek(SeekFrom::End(-1))?;
+        assert!(part.write_all(b"D!").is_err(), "Wrote past the end");
+        //
+        let disk = disk.get_ref();
+        assert_eq!(&disk[1024 * 1024..][..5], b"Start");
+        assert_eq!(&disk[(2 * 1024 * 1024) - 3..][..3], b"EnD");
+        assert_eq!(disk[2 * 1024 * 1024], 0);
+        Ok(())
+    }
+
+    /// Reads stop at the end, seeks can't leave the partition.
+    #[test]
+    fn bounded_read_seek() -> Result {
+        let (gpt, mut disk) = disk()?;
+        let mut part = PartitionIo::new(&gpt, &gpt.partitions()[0], &mut disk);
+        let mut buf = Vec::new();
+        assert_eq!(part.read_to_end(&mut buf)?, 1024 * 1024);
+        assert_eq!(part.read(&mut [0; 1])?, 0);
+        //
+        assert!(part.seek(SeekFrom::End(1)).is_err());
+        assert!(part.seek(SeekFrom::Start(1024 * 1024 + 1)).is_err());
+        assert!(part.seek(SeekFrom::Current(-(1024 * 1024) - 1)).is_err());
+        assert_eq!(part.seek(SeekFrom::Current(-(1024 * 1024)))?, 0);
+        assert_eq!(part.seek(SeekFrom::End(0))?, 1024 * 1024);
+        Ok(())
+    }
+
+    /// Partitions can be copied out of and into an image
+    #[test]
+    fn extract_import() -> Result {
+        let (gpt, mut disk) = disk()?;
+        let part = gpt.partitions()[0];
+        let mut file = Cursor::new(b"Hello".repeat(1000));
+        let copied = import_partition(&gpt, &part, &mut file, &mut disk, CopyOptions::new())?;
+        assert_eq!(copied, Size::from_bytes(5000));
+        assert_eq!(&disk.get_ref()[1024 * 1024..][..5000], &file.get_ref()[..]);
+        //
+        let mut out = Cursor::new(Vec::new());
+        let copied = extract_partition(&gpt, &part, &mut disk, &mut out)?;
+        assert_eq!(copied, Size::from_mib(1));
+        assert_eq!(out.get_ref().len(), 1024 * 1024);
+        assert_eq!(&out.get_ref()[..5000], &file.get_ref()[..]);
+        assert!(out.get_ref()[5000..].iter().all(|b| *b == 0));
+        // Truncated images are an error, not a short copy
+        disk.get_mut().truncate(1024 * 1024 + 5000);
+        match extract_partition(&gpt, &part, &mut disk, Vec::new()) {
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof => (),
+            e => panic!("Truncated image was extracted: {:?}", e),
+        }
+        Ok(())
+    }
+
+    /// Files must fit, and the rest can be zeroed
+    #[test]
+    fn import_fit_zero() -> Result {
+        let (gpt, mut disk) = disk()?;
+        let part = gpt.partitions()[0];
+        let mut file = Cursor::new(vec![1; (1024 * 1024) + 1]);
+        match import_partition(&gpt, &part, &mut file, &mut disk, CopyOptions::new()) {
+            Err(Error::TooLarge) => (),
+            e => panic!("Imported too large file: {:?}", e),
+        }
+        assert!(disk.get_ref()[1024 * 1024..][..1024 * 1024]
+            .iter()
+            .all(|b| *b == 0));
+        //
+        let mut file = Cursor::new(vec![1; 1024 * 1024]);
+        import_partition(&gpt, &part, &mut file, &mut disk, CopyOptions::new())?;
+        let mut file = Cursor::new(vec![2; 512]);
+        let options = CopyOptions::new().zero_fill(true);
+        import_partition(&gpt, &part, &mut file, &mut disk, options)?;
+        let data = &disk.get_ref()[1024 * 1024..][..1024 * 1024];
+        assert!(data[..512].iter().all(|b| *b == 2));
+        assert!(data[512..].iter().all(|b| *b == 0));
+        // Shouldn't have touched anything after the partition
+        assert!(disk.get_ref()[2 * 1024 * 1024..][..1024]
+            .iter()
+            .all(|b| *b == 0));
+        Ok(())
+    }
+
+    /// Sparse extraction creates a correctly sized sparse file
+    #[test]
+    fn extract_sparse() -> Result {
+        let (gpt, mut disk) = disk()?;
+        let part = gpt.partitions()[0];
+        disk.get_mut()[1024 * 1024] = 1;
+        let path = std::env::temp_dir().join(format!("parts-sparse-{}", std::process::id()));
+        let mut out = fs::File::create(&path)?;
+        extract_partition_sparse(&gpt, &part, &mut disk, &mut out)?;
+        drop(out);
+        let data = fs::read(&path)?;
+        fs::remove_file(&path)?;
+        assert_eq!(data.len(), 1024 * 1024);
+        assert_eq!(data[0], 1);
+        assert!(data[1..].iter().all(|b| *b == 0));
+        Ok(())
+    }
+}
@@ -0,0 +1,595 @@
+//! Declarative partition layouts
+//!
+//! Similar to `systemd-repart`, a [`Layout`] describes the partitions a disk
+//! should have, and can be loaded from any format Serde supports, such as
+//! TOML or JSON.
+//!
+//! [`Layout::plan`] compares a layout against an existing [`Gpt`] and returns
+//! a [`Plan`], which only ever appends missing partitions and grows existing
+//! ones into the free space after them.
+//! Existing partitions are never moved or shrunk, so no data is touched.
+//!
+//! # Matching
+//!
+//! Each [`LayoutPartition`], in order, is matched to the first existing
+//! partition not already matched that has the same type and,
+//! if the layout specifies one, the same label.
+//! Layout partitions without a match are created.
+//! Existing partitions without a match are left alone.
+//!
+//! # Sizing
+//!
+//! Matched partitions that aren't the last one on disk can only grow into the
+//! free space directly after them, up to their maximum size.
+//!
+//! The free space after the last partition is shared between that partition,
+//! if matched, and all new partitions.
+//! Each first receives its minimum size, then the rest is distributed
+//! proportionally to their weights, up to their maximum sizes.
+//! New partitions are aligned to, and sized in multiples of, 1MiB.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use parts::{layout::*, Gpt, uuid::Uuid, types::*};
+//! # fn main() -> anyhow::Result<()> {
+//! let layout: Layout = serde_json::from_str(
+//!     r#"{
+//!         "partitions": [
+//!             { "type": "EfiSystem", "label": "ESP", "min-size": 1048576, "max-size": 1048576 },
+//!             { "type": "LinuxFilesystemData", "label": "root" }
+//!         ]
+//!     }"#,
+//! )?;
+//! let mut gpt: Gpt = Gpt::new(Uuid::new_v4(), Size::from_mib(10), BlockSize::new(512));
+//! let plan = layout.plan(&gpt)?;
+//! println!("{}", plan);
+//! plan.apply(&mut gpt)?;
+//! assert_eq!(gpt.partitions().len(), 2);
+//! # Ok(()) }
+//! ```
+use crate::{
+    gpt::{
+        error::*,
+        partition::{Partition, PartitionBuilder},
+    },
+    partitions::PartitionType,
+    types::*,
+    Gpt,
+};
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+use serde_crate::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Alignment, in bytes, of new partitions.
+const ALIGN: u64 = 1024 * 1024;
+
+/// Weight used when none is specified.
+const DEFAULT_WEIGHT: u32 = 1000;
+
+fn default_weight() -> u32 {
+    DEFAULT_WEIGHT
+}
+
+/// The desired partitions of a disk.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(crate = "serde_crate", rename_all = "kebab-case")]
+pub struct Layout {
+    /// Desired partitions, in on-disk order.
+    #[serde(default)]
+    pub partitions: Vec<LayoutPartition>,
+}
+
+/// A desired partition in a [`Layout`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "serde_crate", rename_all = "kebab-case")]
+pub struct LayoutPartition {
+    /// Partition type
+    #[serde(rename = "type")]
+    pub partition_type: PartitionType,
+
+    /// Partition name. Used for matching if not empty.
+    #[serde(default)]
+    pub label: String,
+
+    /// Unique identifer for new partitions. Random by default.
+    #[serde(default)]
+    pub uuid: Option<Uuid>,
+
+    /// Minimum size.
+    ///
+    /// Defaults to one block for existing partitions, and 1MiB for new ones.
+    #[serde(default)]
+    pub min_size: Option<Size>,
+
+    /// Maximum size. Unlimited by default.
+    #[serde(default)]
+    pub max_size: Option<Size>,
+
+    /// Share of the free space, relative to other partitions.
+    ///
+    /// Zero means the partition won't grow past its minimum size.
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+impl LayoutPartition {
+    /// New partition of type `p_type`, with default settings.
+    pub fn new(p_type: PartitionType) -> Self {
+        Self {
+            partition_type: p_type,
+            label: String::new(),
+            uuid: None,
+            min_size: None,
+            max_size: None,
+            weight: DEFAULT_WEIGHT,
+        }
+    }
+}
+
+/// A single change in a [`Plan`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Grow the existing partition at `index`.
+    Grow {
+        /// Index of the partition.
+        index: usize,
+
+        /// Current end of the partition
+        old_end: Block,
+
+        /// New end of the partition
+        new_end: Block,
+    },
+
+    /// Create a new partition.
+    Create(Partition),
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Grow {
+                index,
+                old_end,
+                new_end,
+            } => write!(
+                f,
+                "Grow partition {} from {} to {} (+{} blocks)",
+                index,
+                old_end,
+                new_end,
+                new_end.0 - old_end.0
+            ),
+            Action::Create(part) => write!(
+                f,
+                "Create {} partition \"{}\" ({}) from {} to {} ({} blocks)",
+                part.partition_type(),
+                part.name(),
+                part.uuid(),
+                part.start(),
+                part.end(),
+                (part.end().0 - part.start().0) + 1
+            ),
+        }
+    }
+}
+
+/// Changes needed to make a [`Gpt`] match a [`Layout`].
+///
+/// Nothing is changed until [`Plan::apply`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Plan {
+    actions: Vec<Action>,
+}
+
+impl Plan {
+    /// The planned changes, in the order they will be applied.
+    pub fn actions(&self) -> &[Action] {
+        &self.actions
+    }
+
+    /// Whether the [`Gpt`] already matches the [`Layout`].
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    /// Apply the changes to `gpt`, in memory.
+    ///
+    /// Either all changes are applied, or none are.
+    ///
+    /// # Errors
+    ///
+    /// - If `gpt` changed since the plan was made, and the changes are no
+    ///   longer valid.
+    pub fn apply(&self, gpt: &mut Gpt) -> Result<()> {
+        let mut new = gpt.clone();
+        for action in &self.actions {
+            match action {
+                Action::Grow { index, new_end, .. } => new.resize_partition(*index, *new_end)?,
+                Action::Create(part) => new.add_partition(*part)?,
+            }
+        }
+        *gpt = new;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "Nothing to do");
+        }
+        for action in &self.actions {
+            writeln!(f, "{}", action)?;
+        }
+        Ok(())
+    }
+}
+
+/// Something competing for free space, measured in `unit` blocks.
+struct Claim {
+    min: u64,
+    max: Option<u64>,
+    weight: u32,
+    alloc: u64,
+}
+
+/// Distribute `free` units between `claims`.
+///
+/// Each claim first gets its minimum,
+/// then the rest is shared by weight up to each maximum.
+///
+/// # Errors
+///
+/// - [`Error::NoSpace`] if the minimums don't fit.
+fn distribute(claims: &mut [Claim], mut free: u64) -> Result<()> {
+    for claim in claims.iter_mut() {
+        free = free.checked_sub(claim.min).ok_or(Error::NoSpace)?;
+        claim.alloc = claim.min;
+    }
+    let room = |c: &Claim| c.max.map_or(u64::MAX, |m| m.saturating_sub(c.alloc));
+    loop {
+        let total: u64 = claims
+            .iter()
+            .filter(|c| c.weight > 0 && room(c) > 0)
+            .map(|c| u64::from(c.weight))
+            .sum();
+        if total == 0 || free == 0 {
+            return Ok(());
+        }
+        let share =
+            |c: &Claim| ((u128::from(free) * u128::from(c.weight)) / u128::from(total)) as u64;
+        // Claims that would hit their maximum get exactly that,
+        // and the rest is shared again among the others.
+        if let Some(claim) = claims
+            .iter_mut()
+            .find(|c| c.weight > 0 && room(c) > 0 && share(c) >= room(c))
+        {
+            let room = room(claim);
+            claim.alloc += room;
+            free -= room;
+            continue;
+        }
+        // Anything left over from rounding stays free.
+        for claim in claims.iter_mut().filter(|c| c.weight > 0 && room(c) > 0) {
+            claim.alloc += share(claim);
+        }
+        return Ok(());
+    }
+}
+
+/// `bytes` in blocks, rounded up.
+fn blocks(bytes: Size, block_size: BlockSize) -> u64 {
+    bytes.as_bytes().div_ceil(block_size.get())
+}
+
+impl Layout {
+    /// Plan the changes needed to make `gpt` match this layout.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::NoSpace`] if there isn't enough free space for every
+    ///   partition's minimum size.
+    /// - [`Error::InvalidPartition`] if a label is more than 70 bytes, or a
+    ///   maximum size is less than the minimum.
+    pub fn plan(&self, gpt: &Gpt) -> Result<Plan> {
+        let block_size = gpt.block_size();
+        let unit = (ALIGN / block_size.get()).max(1);
+        let parts = gpt.partitions();
+        //
+        let mut used = Vec::new();
+        used.resize(parts.len(), false);
+        let mut matches = Vec::with_capacity(self.partitions.len());
+        for want in &self.partitions {
+            if want.label.len() > 70 {
+                return Err(Error::InvalidPartition("Partition label too long"));
+            }
+            if let (Some(min), Some(max)) = (want.min_size, want.max_size) {
+                if max < min {
+                    return Err(Error::InvalidPartition("Maximum size is less than minimum"));
+                }
+            }
+            let found = parts.iter().enumerate().position(|(i, p)| {
+                !used[i]
+                    && p.partition_type() == want.partition_type
+                    && (want.label.is_empty() || p.name() == want.label)
+            });
+            if let Some(i) = found {
+                used[i] = true;
+            }
+            matches.push(found);
+        }
+        let wanted = |index: usize| {
+            matches
+                .iter()
+                .position(|m| *m == Some(index))
+                .map(|i| &self.partitions[i])
+        };
+        //
+        let mut actions = Vec::new();
+        // Matched partitions with another after them only have one gap.
+        for (index, part) in parts.iter().enumerate().rev().skip(1).rev() {
+            let want = match wanted(index) {
+                Some(want) => want,
+                None => continue,
+            };
+            let gap_end = parts[index + 1].start() - 1;
+            let len = (part.end().0 - part.start().0) + 1;
+            let min = want.min_size.map_or(1, |s| blocks(s, block_size));
+            let max = want.max_size.map_or(u64::MAX, |s| blocks(s, block_size));
+            let target = if want.weight > 0 {
+                max
+            } else {
+                min.max(len).min(max)
+            };
+            let end = Block(
+                part.start()
+                    .0
+                    .saturating_add(target.max(len) - 1)
+                    .min(gap_end.0),
+            );
+            if (end.0 - part.start().0) + 1 < min {
+                return Err(Error::NoSpace);
+            }
+            if end > part.end() {
+                actions.push(Action::Grow {
+                    index,
+                    old_end: part.end(),
+                    new_end: end,
+                });
+            }
+        }
+        // Everything after the last partition
+        let gap_start = parts
+            .last()
+            .map_or_else(|| gpt.first_usable(), |p| p.end() + 1);
+        let aligned = Block(gap_start.0.div_ceil(unit) * unit);
+        let free = (gpt.last_usable().0 + 1).saturating_sub(aligned.0) / unit;
+        let last = parts
+            .len()
+            .checked_sub(1)
+            .and_then(|i| wanted(i).map(|w| (i, w)));
+        let mut claims = Vec::new();
+        if let Some((index, want)) = last {
+            let part = parts[index];
+            // End of the partition if it grows by `n` units
+            let units_for = |len: u64| {
+                (part.start().0 + len)
+                    .saturating_sub(aligned.0)
+                    .div_ceil(unit)
+            };
+            let len = (part.end().0 - part.start().0) + 1;
+            let min = want.min_size.map_or(0, |s| {
+                let min = blocks(s, block_size);
+                if min > len {
+                    units_for(min)
+                } else {
+                    0
+                }
+            });
+            let max = want.max_size.map(|s| {
+                let max = blocks(s, block_size);
+                (part.start().0 + max).saturating_sub(aligned.0) / unit
+            });
+            claims.push(Claim {
+                min,
+                max,
+                weight: want.weight,
+                alloc: 0,
+            });
+        }
+        let new: Vec<_> = self
+            .partitions
+            .iter()
+            .zip(&matches)
+            .filter(|(_, m)| m.is_none())
+            .map(|(w, _)| w)
+            .collect();
+        for want in &new {
+            let min = want
+                .min_size
+                .map_or(1, |s| blocks(s, block_size).div_ceil(unit))
+                .max(1);
+            let max = want
+                .max_size
+                .map(|s| (blocks(s, block_size) / unit).max(min));
+            claims.push(Claim {
+                min,
+                max,
+                weight: want.weight,
+                alloc: 0,
+            });
+        }
+        distribute(&mut claims, free)?;
+        //
+        let mut claims = claims.iter();
+        let mut next = aligned;
+        if let Some((index, _)) = last {
+            let claim = claims.next().expect("BUG: Missing claim");
+            if claim.alloc > 0 {
+                let new_end = next + (claim.alloc * unit) - 1;
+                actions.push(Action::Grow {
+                    index,
+                    old_end: parts[index].end(),
+                    new_end,
+                });
+                next = new_end + 1;
+            }
+        }
+        for (want, claim) in new.iter().zip(claims) {
+            let part = PartitionBuilder::new(want.uuid.unwrap_or_else(Uuid::new_v4), gpt)
+                .start(next)
+                .end(next + (claim.alloc * unit) - 1)
+                .partition_type(want.partition_type)
+                .name(&want.label)
+                .finish();
+            next = part.end() + 1;
+            actions.push(Action::Create(part));
+        }
+        Ok(Plan { actions })
+    }
+
+    /// Plan and apply the changes needed to make `gpt` match this layout,
+    /// in memory.
+    ///
+    /// Returns the [`Plan`] that was applied.
+    ///
+    /// See [`Layout::plan`] and [`Plan::apply`] for details.
+    pub fn apply(&self, gpt: &mut Gpt) -> Result<Plan> {
+        let plan = self.plan(gpt)?;
+        plan.apply(gpt)?;
+        Ok(plan)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::util::{Result, BLOCK_SIZE};
+    use pretty_assertions::assert_eq;
+
+    fn gpt() -> Gpt {
+        Gpt::new(Uuid::nil(), Size::from_mib(10), BLOCK_SIZE)
+    }
+
+    fn part(p_type: PartitionType, label: &str) -> LayoutPartition {
+        let mut part = LayoutPartition::new(p_type);
+        part.label = label.into();
+        part
+    }
+
+    /// Layouts can be loaded from JSON
+    #[test]
+    fn deserialize() -> Result {
+        let layout: Layout = serde_json::from_str(
+            r#"{
+                "partitions": [
+                    { "type": "EfiSystem", "label": "ESP", "min-size": 1048576 },
+                    { "type": "LinuxSwap", "weight": 0 },
+                    { "type": { "Unknown": "01234567-89AB-CDEF-0123-456789ABCDEF" } }
+                ]
+            }"#,
+        )?;
+        assert_eq!(layout.partitions.len(), 3);
+        assert_eq!(layout.partitions[0].label, "ESP");
+        assert_eq!(layout.partitions[0].min_size, Some(Size::from_mib(1)));
+        assert_eq!(layout.partitions[0].weight, DEFAULT_WEIGHT);
+        assert_eq!(layout.partitions[1].weight, 0);
+        Ok(())
+    }
+
+    /// New partitions share the free space by weight
+    #[test]
+    fn empty_disk() -> Result {
+        let mut gpt = gpt();
+        let mut esp = part(PartitionType::EfiSystem, "ESP");
+        esp.max_size = Some(Size::from_mib(1));
+        let mut swap = part(PartitionType::LinuxSwap, "swap");
+        swap.weight = 0;
+        swap.min_size = Some(Size::from_mib(2));
+        let root = part(PartitionType::LinuxFilesystemData, "root");
+        let layout = Layout {
+            partitions: vec![esp, swap, root],
+        };
+        let plan = layout.apply(&mut gpt)?;
+        assert_eq!(plan.actions().len(), 3);
+        let parts = gpt.partitions();
+        assert_eq!(parts[0].name(), "ESP");
+        assert_eq!(parts[0].start(), Block(2048));
+        assert_eq!(parts[0].end(), Block(4095));
+        assert_eq!(parts[1].name(), "swap");
+        assert_eq!(parts[1].start(), Block(4096));
+        assert_eq!(parts[1].end(), Block(8191));
+        // Everything else, whole MiB only
+        assert_eq!(parts[2].name(), "root");
+        assert_eq!(parts[2].start(), Block(8192));
+        assert_eq!(parts[2].end(), Block(18431));
+        // Applying again does nothing
+        assert!(layout.plan(&gpt)?.is_empty());
+        Ok(())
+    }
+
+    /// Existing partitions grow, missing ones are appended
+    #[test]
+    fn existing() -> Result {
+        let mut gpt = gpt();
+        for (start, name) in &[(2048, "a"), (6144, "b")] {
+            let part = PartitionBuilder::new(Uuid::new_v4(), &gpt)
+                .start(Block(*start))
+                .size(Size::from_mib(1))
+                .partition_type(PartitionType::LinuxFilesystemData)
+                .name(name)
+                .finish();
+            gpt.add_partition(part)?;
+        }
+        let before = gpt.clone();
+        let mut new = part(PartitionType::LinuxSwap, "new");
+        new.weight = 0;
+        let layout = Layout {
+            partitions: vec![
+                part(PartitionType::LinuxFilesystemData, "a"),
+                part(PartitionType::LinuxFilesystemData, "b"),
+                new,
+            ],
+        };
+        let plan = layout.plan(&gpt)?;
+        assert_eq!(gpt, before, "Planning changed the Gpt");
+        assert_eq!(
+            plan.actions()[0],
+            Action::Grow {
+                index: 0,
+                old_end: Block(4095),
+                new_end: Block(6143)
+            }
+        );
+        plan.apply(&mut gpt)?;
+        let parts = gpt.partitions();
+        assert_eq!(parts.len(), 3);
+        // `b` and `new` share the end, `new` only gets its minimum.
+        assert_eq!(parts[1].end(), Block(18431 - 2048));
+        assert_eq!(parts[2].name(), "new");
+        assert_eq!(parts[2].start(), Block(18432 - 2048));
+        assert_eq!(parts[2].end(), Block(18431));
+        // Existing partitions never move
+        assert_eq!(parts[0].start(), Block(2048));
+        assert_eq!(parts[1].start(), Block(6144));
+        Ok(())
+    }
+
+    /// Minimum sizes must fit
+    #[test]
+    fn no_space() {
+        let gpt = gpt();
+        let mut big = part(PartitionType::LinuxFilesystemData, "big");
+        big.min_size = Some(Size::from_mib(10));
+        let layout = Layout {
+            partitions: vec![big],
+        };
+        match layout.plan(&gpt) {
+            Err(Error::NoSpace) => (),
+            e => panic!("Partition larger than the disk was planned: {:?}", e),
+        }
+    }
+}
@@ -6,9 +6,10 @@
 //! # Features
 //!
 //! - `default`: `serde` and `std`.
-//! - `serde`: [`PartitionType`], [`uuid::Uuid`], everything in [`types`] become
-//!   Serde compatible.
+//! - `serde`: [`Gpt`], [`Partition`], [`PartitionType`], [`uuid::Uuid`],
+//!   everything in [`types`] become Serde compatible.
 //! - `std`: Standard library support and integration.
+//! - `cli`: Build the `parts` command line tool. Not useful to libraries.
 //!
 //! # Usage
 //!
@@ -45,16 +46,31 @@ extern crate alloc;
 #[cfg(all(test, feature = "std"))]
 mod util;
 
+#[cfg(feature = "std")]
+pub mod backup;
+pub mod device;
 mod gpt;
+pub mod hybrid;
+#[cfg(feature = "std")]
+pub mod image;
+#[cfg(feature = "std")]
+pub mod io;
+#[cfg(all(feature = "serde", feature = "alloc"))]
+pub mod layout;
 mod mbr;
 mod partitions;
+pub mod probe;
+pub mod sfdisk;
 pub mod types;
+pub mod write;
 
 pub use gpt::{
     error::Error,
     partition::{Partition, PartitionBuilder},
     Gpt,
     GptC,
+    GptHelper,
+    Status,
 };
 pub use partitions::PartitionType;
 
@@ -102,6 +102,25 @@ impl ProtectiveMbr {
         Ok(mbr)
     }
 
+    /// Check that `source` has a valid MBR signature, without requiring it
+    /// to be protective.
+    ///
+    /// Used for hybrid MBRs, such as in isohybrid images.
+    ///
+    /// # Errors
+    ///
+    /// - If the signature is invalid
+    /// - [`Error::NotEnough`] if `source` is not [`MBR_SIZE`] bytes.
+    pub fn check_signature(source: &[u8]) -> Result<()> {
+        if source.len() != MBR_SIZE {
+            return Err(Error::NotEnough);
+        }
+        if source[MBR_SIZE - 2..] != [0x55, 0xAA] {
+            return Err(Error::Invalid("MBR signature invalid"));
+        }
+        Ok(())
+    }
+
     /// Write a GPT Protective MBR to `dest`
     ///
     /// # Errors
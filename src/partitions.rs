@@ -1,4 +1,6 @@
 //! Known partition types
+use crate::Error;
+use core::str::FromStr;
 use derive_more::Display;
 #[cfg(feature = "serde")]
 use serde_crate::{Deserialize, Serialize};
@@ -61,6 +63,26 @@ macro_rules! partition_type {
             }
         }
 
+        /// Parse a [`PartitionType`] from its name, ignoring case,
+        /// or its GUID.
+        ///
+        /// This is the inverse of the [`Display`](core::fmt::Display)
+        /// implementation.
+        impl FromStr for PartitionType {
+            type Err = Error;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                $(
+                    if s.eq_ignore_ascii_case(stringify!($name)) {
+                        return Ok(Self::$name);
+                    }
+                )+
+                Uuid::parse_str(s)
+                    .map(Self::from_uuid)
+                    .map_err(|_| Error::InvalidPartition("Unknown partition type"))
+            }
+        }
+
         impl Default for PartitionType {
             fn default() -> Self {
                 // INFO: This is kinda hacky, it depends on `Unused` being defined
@@ -158,4 +180,24 @@ mod tests {
             "LinuxFilesystemData"
         );
     }
+
+    #[test]
+    fn from_str() {
+        assert_eq!(
+            "linuxfilesystemdata".parse::<PartitionType>().unwrap(),
+            PartitionType::LinuxFilesystemData
+        );
+        assert_eq!(
+            "c12a7328-f81f-11d2-ba4b-00a0c93ec93b"
+                .parse::<PartitionType>()
+                .unwrap(),
+            PartitionType::EfiSystem
+        );
+        let unknown = PartitionType::Unknown(Uuid::from_u128(1));
+        assert_eq!(
+            unknown.to_string().parse::<PartitionType>().unwrap(),
+            unknown
+        );
+        assert!("NotAType".parse::<PartitionType>().is_err());
+    }
 }
@@ -0,0 +1,751 @@
+//! Detect what is inside a partition
+//!
+//! Similar to `blkid`, this looks for well known on-disk signatures,
+//! and reports the kind of content along with its UUID, serial number and
+//! label, when available.
+use crate::{
+    gpt::{error::*, partition::Partition, ChunkBuf, GptHelper, CHUNK_SIZE},
+    types::*,
+    GptC,
+};
+use arrayvec::ArrayString;
+use core::{char::REPLACEMENT_CHARACTER, convert::TryInto, str};
+use derive_more::Display;
+use uuid::Uuid;
+
+/// Known partition contents.
+///
+/// This enum is `non_exhaustive`, be prepared for matches to change.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Display)]
+#[non_exhaustive]
+pub enum ContentType {
+    /// Linux ext2 filesystem
+    Ext2,
+
+    /// Linux ext3 filesystem
+    Ext3,
+
+    /// Linux ext4 filesystem
+    Ext4,
+
+    /// XFS filesystem
+    Xfs,
+
+    /// Btrfs filesystem
+    Btrfs,
+
+    /// FAT12 filesystem
+    Fat12,
+
+    /// FAT16 filesystem
+    Fat16,
+
+    /// FAT32 filesystem
+    Fat32,
+
+    /// exFAT filesystem
+    ExFat,
+
+    /// NTFS filesystem
+    Ntfs,
+
+    /// Linux swap space
+    Swap,
+
+    /// LVM2 Physical Volume
+    Lvm2,
+
+    /// LUKS1 encrypted volume
+    Luks1,
+
+    /// LUKS2 encrypted volume
+    Luks2,
+
+    /// Linux software RAID member
+    MdRaid,
+
+    /// ZFS pool member
+    Zfs,
+
+    /// ISO9660 filesystem
+    Iso9660,
+}
+
+/// Detected partition contents.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Content {
+    kind: ContentType,
+    uuid: Option<Uuid>,
+    serial: Option<u64>,
+    label: ArrayString<[u8; 256]>,
+}
+
+impl Content {
+    fn new(kind: ContentType) -> Self {
+        Self {
+            kind,
+            uuid: None,
+            serial: None,
+            label: ArrayString::new(),
+        }
+    }
+
+    fn uuid(mut self, uuid: Uuid) -> Self {
+        if !uuid.is_nil() {
+            self.uuid = Some(uuid);
+        }
+        self
+    }
+
+    fn serial(mut self, serial: u64) -> Self {
+        self.serial = Some(serial);
+        self
+    }
+
+    /// Set the label from raw on-disk bytes.
+    ///
+    /// Trailing NULs and spaces are removed,
+    /// invalid UTF-8 is replaced.
+    fn label(mut self, raw: &[u8]) -> Self {
+        let end = raw.iter().position(|b| *b == 0).unwrap_or(raw.len());
+        let mut raw = &raw[..end];
+        while let Some((b' ', rest)) = raw.split_last() {
+            raw = rest;
+        }
+        self.label.clear();
+        loop {
+            match str::from_utf8(raw) {
+                Ok(s) => {
+                    let _ = self.label.try_push_str(s);
+                    break;
+                }
+                Err(e) => {
+                    let (valid, rest) = raw.split_at(e.valid_up_to());
+                    // SAFETY: `valid_up_to` says so.
+                    let _ = self
+                        .label
+                        .try_push_str(unsafe { str::from_utf8_unchecked(valid) });
+                    let _ = self.label.try_push(REPLACEMENT_CHARACTER);
+                    raw = &rest[e.error_len().unwrap_or(rest.len())..];
+                }
+            }
+        }
+        self
+    }
+}
+
+impl Content {
+    /// The kind of content
+    pub fn content_type(&self) -> ContentType {
+        self.kind
+    }
+
+    /// Filesystem or volume UUID, if it has one.
+    pub fn content_uuid(&self) -> Option<Uuid> {
+        self.uuid
+    }
+
+    /// Volume serial number, for filesystems that use one instead of a UUID.
+    ///
+    /// FAT and exFAT serials are 32 bits, NTFS serials are 64 bits.
+    pub fn content_serial(&self) -> Option<u64> {
+        self.serial
+    }
+
+    /// Filesystem or volume label, if it has one.
+    pub fn content_label(&self) -> Option<&str> {
+        if self.label.is_empty() {
+            None
+        } else {
+            Some(&self.label)
+        }
+    }
+}
+
+/// Probe `size` bytes of content using `func`.
+///
+/// `func` receives a byte offset relative to the start of the content,
+/// and a buffer to read into.
+/// Reads are of 4096 byte chunks, starting on a multiple of 4096, into
+/// buffers aligned to 4096 bytes. Only the last chunk may be shorter,
+/// if `size` isn't a multiple of 4096.
+/// It will never be asked to read beyond `size`.
+///
+/// Returns `None` if nothing recognizable was found.
+///
+/// # Errors
+///
+/// - If `func` does.
+pub fn probe<F: FnMut(Offset, &mut [u8]) -> Result<()>>(
+    func: F,
+    size: Size,
+) -> Result<Option<Content>> {
+    let mut dev = Probe {
+        func,
+        size,
+        chunk: ChunkBuf::new(),
+        cached: None,
+    };
+    let probes: &[ProbeFn<F>] = &[
+        Probe::luks,
+        Probe::mdraid,
+        Probe::lvm2,
+        Probe::zfs,
+        Probe::swap,
+        Probe::xfs,
+        Probe::btrfs,
+        Probe::ext,
+        Probe::iso9660,
+        Probe::ntfs,
+        Probe::exfat,
+        Probe::fat,
+    ];
+    for probe in probes {
+        if let Some(content) = probe(&mut dev)? {
+            return Ok(Some(content));
+        }
+    }
+    Ok(None)
+}
+
+/// Probe the contents of `part`, from `gpt`, using `func`.
+///
+/// Unlike [`probe`], `func` receives byte offsets relative to the start of
+/// the device. Reads are still of 4096 byte chunks from the start of
+/// the partition, so they're whole, aligned blocks, like with
+/// [`GptC::from_bytes_with_func`].
+///
+/// # Errors
+///
+/// - If `func` does.
+pub fn probe_partition<C, F>(
+    gpt: &GptC<C>,
+    part: &Partition,
+    mut func: F,
+) -> Result<Option<Content>>
+where
+    C: GptHelper<C>,
+    F: FnMut(Offset, &mut [u8]) -> Result<()>,
+{
+    let start = gpt.partition_offset(part);
+    probe(
+        |offset, buf| func(Offset(start.0 + offset.0), buf),
+        gpt.partition_size(part),
+    )
+}
+
+/// Little-endian `u16` at `at`
+fn le16(buf: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes(buf[at..][..2].try_into().unwrap())
+}
+
+/// Little-endian `u32` at `at`
+fn le32(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(buf[at..][..4].try_into().unwrap())
+}
+
+/// Little-endian `u64` at `at`
+fn le64(buf: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(buf[at..][..8].try_into().unwrap())
+}
+
+/// Big-endian UUID bytes at `at`
+fn uuid_at(buf: &[u8], at: usize) -> Uuid {
+    Uuid::from_bytes(buf[at..][..16].try_into().unwrap())
+}
+
+/// UUID stored as an ASCII string of `len` bytes at `at`
+fn uuid_str_at(buf: &[u8], at: usize, len: usize) -> Option<Uuid> {
+    let raw = &buf[at..][..len];
+    let end = raw.iter().position(|b| *b == 0).unwrap_or(len);
+    str::from_utf8(&raw[..end])
+        .ok()
+        .and_then(|s| Uuid::parse_str(s).ok())
+}
+
+/// A single content probe
+type ProbeFn<F> = fn(&mut Probe<F>) -> Result<Option<Content>>;
+
+/// Bounds checked reads, in aligned chunks
+struct Probe<F> {
+    func: F,
+    size: Size,
+
+    /// The last chunk read
+    chunk: ChunkBuf,
+
+    /// Offset of `chunk`, if any
+    cached: Option<u64>,
+}
+
+impl<F: FnMut(Offset, &mut [u8]) -> Result<()>> Probe<F> {
+    /// Read `buf` from `offset`, through the chunks containing it.
+    ///
+    /// Returns `false`, without reading, if `buf` doesn't fit in the content.
+    fn read(&mut self, offset: u64, buf: &mut [u8]) -> Result<bool> {
+        let size = self.size.as_bytes();
+        match offset.checked_add(buf.len() as u64) {
+            Some(end) if end <= size => (),
+            _ => return Ok(false),
+        }
+        let mut pos = 0;
+        while pos < buf.len() {
+            let at = offset + pos as u64;
+            let start = at - (at % CHUNK_SIZE as u64);
+            let len = (size - start).min(CHUNK_SIZE as u64) as usize;
+            if self.cached != Some(start) {
+                self.cached = None;
+                (self.func)(Offset(start), &mut self.chunk.0[..len])?;
+                self.cached = Some(start);
+            }
+            let chunk = &self.chunk.0[(at - start) as usize..len];
+            let n = chunk.len().min(buf.len() - pos);
+            buf[pos..][..n].copy_from_slice(&chunk[..n]);
+            pos += n;
+        }
+        Ok(true)
+    }
+
+    /// LUKS1 and LUKS2
+    fn luks(&mut self) -> Result<Option<Content>> {
+        let mut buf = [0; 512];
+        if !self.read(0, &mut buf)? || &buf[..6] != b"LUKS\xba\xbe" {
+            return Ok(None);
+        }
+        let content = match u16::from_be_bytes([buf[6], buf[7]]) {
+            1 => Content::new(ContentType::Luks1),
+            2 => Content::new(ContentType::Luks2).label(&buf[24..][..48]),
+            _ => return Ok(None),
+        };
+        Ok(Some(match uuid_str_at(&buf, 168, 40) {
+            Some(uuid) => content.uuid(uuid),
+            None => content,
+        }))
+    }
+
+    /// Linux software RAID, metadata 0.90 and 1.x
+    fn mdraid(&mut self) -> Result<Option<Content>> {
+        const MAGIC: u32 = 0xA92B_4EFC;
+        let size = self.size.as_bytes();
+        let sectors = size / 512;
+        let mut buf = [0; 256];
+        // Version 1.1, 1.2, and 1.0
+        let mut offsets = [0, 4096, 0];
+        offsets[2] = (sectors.saturating_sub(16) & !7) * 512;
+        for &offset in &offsets {
+            if self.read(offset, &mut buf)? && le32(&buf, 0) == MAGIC && le32(&buf, 4) == 1 {
+                let content = Content::new(ContentType::MdRaid)
+                    .uuid(uuid_at(&buf, 16))
+                    .label(&buf[32..][..32]);
+                return Ok(Some(content));
+            }
+        }
+        // Version 0.90, 64KiB aligned and 64KiB from the end
+        const RESERVED: u64 = 64 * 1024;
+        let offset = (size & !(RESERVED - 1)).saturating_sub(RESERVED);
+        if self.read(offset, &mut buf)? && le32(&buf, 0) == MAGIC && le32(&buf, 4) == 0 {
+            return Ok(Some(Content::new(ContentType::MdRaid)));
+        }
+        Ok(None)
+    }
+
+    /// LVM2 Physical Volume label, in one of the first four sectors
+    fn lvm2(&mut self) -> Result<Option<Content>> {
+        let mut buf = [0; 512];
+        for sector in 0..4 {
+            if !self.read(sector * 512, &mut buf)? {
+                break;
+            }
+            if &buf[..8] == b"LABELONE" && &buf[24..32] == b"LVM2 001" {
+                return Ok(Some(Content::new(ContentType::Lvm2)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// ZFS, by looking for an uberblock in the first label
+    fn zfs(&mut self) -> Result<Option<Content>> {
+        const MAGIC: u64 = 0x00BA_B10C;
+        let mut buf = [0; 8];
+        // Uberblocks are at least 1KiB, and start 128KiB into the label.
+        for i in 0..32 {
+            if !self.read((128 * 1024) + (i * 1024), &mut buf)? {
+                break;
+            }
+            let magic = u64::from_le_bytes(buf);
+            if magic == MAGIC || magic.swap_bytes() == MAGIC {
+                return Ok(Some(Content::new(ContentType::Zfs)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Linux swap, for the common page sizes.
+    fn swap(&mut self) -> Result<Option<Content>> {
+        let mut magic = [0; 10];
+        for &page in &[4096, 8192, 16384, 65536] {
+            if !self.read(page - 10, &mut magic)? {
+                break;
+            }
+            match &magic {
+                b"SWAPSPACE2" => {
+                    let mut buf = [0; 44];
+                    self.read(1024, &mut buf)?;
+                    let content = Content::new(ContentType::Swap)
+                        .uuid(uuid_at(&buf, 12))
+                        .label(&buf[28..][..16]);
+                    return Ok(Some(content));
+                }
+                b"SWAP-SPACE" => return Ok(Some(Content::new(ContentType::Swap))),
+                _ => (),
+            }
+        }
+        Ok(None)
+    }
+
+    /// XFS
+    fn xfs(&mut self) -> Result<Option<Content>> {
+        let mut buf = [0; 120];
+        if !self.read(0, &mut buf)? || &buf[..4] != b"XFSB" {
+            return Ok(None);
+        }
+        let content = Content::new(ContentType::Xfs)
+            .uuid(uuid_at(&buf, 32))
+            .label(&buf[108..][..12]);
+        Ok(Some(content))
+    }
+
+    /// Btrfs
+    fn btrfs(&mut self) -> Result<Option<Content>> {
+        let mut buf = [0; 0x22B];
+        if !self.read(64 * 1024, &mut buf)? || &buf[0x40..][..8] != b"_BHRfS_M" {
+            return Ok(None);
+        }
+        let content = Content::new(ContentType::Btrfs)
+            .uuid(uuid_at(&buf, 0x20))
+            .label(&buf[0x12B..][..256]);
+        Ok(Some(content))
+    }
+
+    /// ext2, ext3, and ext4
+    fn ext(&mut self) -> Result<Option<Content>> {
+        /// Journal
+        const COMPAT_HAS_JOURNAL: u32 = 0x4;
+        /// External journal device
+        const INCOMPAT_JOURNAL_DEV: u32 = 0x8;
+        /// Features ext3 doesn't support
+        const INCOMPAT_EXT4: u32 = 0x40 | 0x80 | 0x100 | 0x200 | 0x8000;
+        const RO_COMPAT_EXT4: u32 = 0x8 | 0x10 | 0x20 | 0x40 | 0x400;
+        //
+        let mut buf = [0; 0x88];
+        if !self.read(1024, &mut buf)? || le16(&buf, 0x38) != 0xEF53 {
+            return Ok(None);
+        }
+        let compat = le32(&buf, 0x5C);
+        let incompat = le32(&buf, 0x60);
+        let ro_compat = le32(&buf, 0x64);
+        if incompat & INCOMPAT_JOURNAL_DEV != 0 {
+            return Ok(None);
+        }
+        let kind = if incompat & INCOMPAT_EXT4 != 0 || ro_compat & RO_COMPAT_EXT4 != 0 {
+            ContentType::Ext4
+        } else if compat & COMPAT_HAS_JOURNAL != 0 {
+            ContentType::Ext3
+        } else {
+            ContentType::Ext2
+        };
+        let content = Content::new(kind)
+            .uuid(uuid_at(&buf, 0x68))
+            .label(&buf[0x78..][..16]);
+        Ok(Some(content))
+    }
+
+    /// ISO9660, from the Primary Volume Descriptor
+    fn iso9660(&mut self) -> Result<Option<Content>> {
+        let mut buf = [0; 72];
+        if !self.read(16 * 2048, &mut buf)? || buf[0] != 1 || &buf[1..6] != b"CD001" {
+            return Ok(None);
+        }
+        Ok(Some(
+            Content::new(ContentType::Iso9660).label(&buf[40..][..32]),
+        ))
+    }
+
+    /// NTFS
+    fn ntfs(&mut self) -> Result<Option<Content>> {
+        let mut buf = [0; 512];
+        if !self.read(0, &mut buf)? || &buf[3..11] != b"NTFS    " {
+            return Ok(None);
+        }
+        Ok(Some(
+            Content::new(ContentType::Ntfs).serial(le64(&buf, 0x48)),
+        ))
+    }
+
+    /// exFAT
+    fn exfat(&mut self) -> Result<Option<Content>> {
+        let mut buf = [0; 512];
+        if !self.read(0, &mut buf)? || &buf[3..11] != b"EXFAT   " {
+            return Ok(None);
+        }
+        Ok(Some(
+            Content::new(ContentType::ExFat).serial(le32(&buf, 100).into()),
+        ))
+    }
+
+    /// FAT12, FAT16, and FAT32.
+    ///
+    /// The FAT type is determined by the cluster count, per the FAT spec.
+    fn fat(&mut self) -> Result<Option<Content>> {
+        let mut buf = [0; 512];
+        if !self.read(0, &mut buf)? || buf[510..] != [0x55, 0xAA] {
+            return Ok(None);
+        }
+        if buf[0] != 0xEB && buf[0] != 0xE9 {
+            return Ok(None);
+        }
+        let bytes_per_sector = u64::from(le16(&buf, 11));
+        let sectors_per_cluster = u64::from(buf[13]);
+        let reserved = u64::from(le16(&buf, 14));
+        let fats = u64::from(buf[16]);
+        let root_entries = u64::from(le16(&buf, 17));
+        if !bytes_per_sector.is_power_of_two()
+            || !(512..=4096).contains(&bytes_per_sector)
+            || !sectors_per_cluster.is_power_of_two()
+            || reserved == 0
+            || fats == 0
+        {
+            return Ok(None);
+        }
+        let total = match le16(&buf, 19) {
+            0 => u64::from(le32(&buf, 32)),
+            n => u64::from(n),
+        };
+        let fat_size = match le16(&buf, 22) {
+            0 => u64::from(le32(&buf, 36)),
+            n => u64::from(n),
+        };
+        let root_sectors = (root_entries * 32).div_ceil(bytes_per_sector);
+        let meta = reserved + (fats * fat_size) + root_sectors;
+        let clusters = match total.checked_sub(meta) {
+            Some(data) => data / sectors_per_cluster,
+            None => return Ok(None),
+        };
+        let (kind, ext) = if clusters < 4085 {
+            (ContentType::Fat12, 36)
+        } else if clusters < 65525 {
+            (ContentType::Fat16, 36)
+        } else {
+            (ContentType::Fat32, 64)
+        };
+        let mut content = Content::new(kind);
+        // Extended boot signature, the serial and label are valid.
+        if buf[ext + 2] == 0x29 {
+            content = content.serial(le32(&buf, ext + 3).into());
+            let label = &buf[ext + 7..][..11];
+            if label != b"NO NAME    " {
+                content = content.label(label);
+            }
+        }
+        Ok(Some(content))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    const LABEL: &str = "Label";
+    const UUID: &str = "01234567-89AB-CDEF-0123-456789ABCDEF";
+
+    fn uuid() -> Uuid {
+        Uuid::parse_str(UUID).unwrap()
+    }
+
+    /// Probe `data`
+    fn probe_data(data: &[u8]) -> Option<Content> {
+        probe(
+            |offset, buf| {
+                assert_eq!(offset.0 % 4096, 0, "Unaligned offset {}", offset);
+                assert_eq!(buf.as_ptr() as usize % 4096, 0, "Unaligned buffer");
+                assert!(buf.len() <= 4096, "Large read at {}", offset);
+                buf.copy_from_slice(&data[offset.0 as usize..][..buf.len()]);
+                Ok(())
+            },
+            Size::from_bytes(data.len() as u64),
+        )
+        .unwrap()
+    }
+
+    fn put(data: &mut [u8], at: usize, bytes: &[u8]) {
+        data[at..][..bytes.len()].copy_from_slice(bytes);
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(probe_data(&[0; 1024 * 1024]), None);
+        // Too small for anything, shouldn't read out of bounds.
+        assert_eq!(probe_data(&[0; 16]), None);
+    }
+
+    #[test]
+    fn ext() {
+        let mut data = vec![0; 1024 * 1024];
+        put(&mut data, 1024 + 0x38, &0xEF53u16.to_le_bytes());
+        put(&mut data, 1024 + 0x68, uuid().as_bytes());
+        put(&mut data, 1024 + 0x78, LABEL.as_bytes());
+        let content = probe_data(&data).unwrap();
+        assert_eq!(content.content_type(), ContentType::Ext2);
+        assert_eq!(content.content_uuid(), Some(uuid()));
+        assert_eq!(content.content_label(), Some(LABEL));
+        //
+        put(&mut data, 1024 + 0x5C, &4u32.to_le_bytes());
+        assert_eq!(probe_data(&data).unwrap().content_type(), ContentType::Ext3);
+        put(&mut data, 1024 + 0x60, &0x40u32.to_le_bytes());
+        assert_eq!(probe_data(&data).unwrap().content_type(), ContentType::Ext4);
+    }
+
+    #[test]
+    fn xfs_btrfs() {
+        let mut data = vec![0; 1024 * 1024];
+        put(&mut data, 0, b"XFSB");
+        put(&mut data, 32, uuid().as_bytes());
+        put(&mut data, 108, LABEL.as_bytes());
+        let content = probe_data(&data).unwrap();
+        assert_eq!(content.content_type(), ContentType::Xfs);
+        assert_eq!(content.content_uuid(), Some(uuid()));
+        assert_eq!(content.content_label(), Some(LABEL));
+        //
+        let mut data = vec![0; 1024 * 1024];
+        put(&mut data, 0x10040, b"_BHRfS_M");
+        put(&mut data, 0x10020, uuid().as_bytes());
+        put(&mut data, 0x1012B, LABEL.as_bytes());
+        let content = probe_data(&data).unwrap();
+        assert_eq!(content.content_type(), ContentType::Btrfs);
+        assert_eq!(content.content_uuid(), Some(uuid()));
+        assert_eq!(content.content_label(), Some(LABEL));
+    }
+
+    /// Minimal FAT boot sector
+    fn fat(total_sectors: u32, fat_size: u32, root_entries: u16) -> Vec<u8> {
+        let mut data = vec![0; 1024 * 1024];
+        data[0] = 0xEB;
+        put(&mut data, 11, &512u16.to_le_bytes());
+        data[13] = 1;
+        put(&mut data, 14, &1u16.to_le_bytes());
+        data[16] = 2;
+        put(&mut data, 17, &root_entries.to_le_bytes());
+        put(&mut data, 32, &total_sectors.to_le_bytes());
+        put(&mut data, 510, &[0x55, 0xAA]);
+        if root_entries == 0 {
+            put(&mut data, 36, &fat_size.to_le_bytes());
+            data[66] = 0x29;
+            put(&mut data, 67, &0x1234_ABCDu32.to_le_bytes());
+            put(&mut data, 71, b"LABEL      ");
+        } else {
+            put(&mut data, 22, &(fat_size as u16).to_le_bytes());
+            data[38] = 0x29;
+            put(&mut data, 39, &0x1234_ABCDu32.to_le_bytes());
+            put(&mut data, 43, b"NO NAME    ");
+        }
+        data
+    }
+
+    #[test]
+    fn fat_types() {
+        let content = probe_data(&fat(2048, 6, 512)).unwrap();
+        assert_eq!(content.content_type(), ContentType::Fat12);
+        assert_eq!(content.content_serial(), Some(0x1234_ABCD));
+        assert_eq!(content.content_label(), None);
+        let content = probe_data(&fat(20_000, 80, 512)).unwrap();
+        assert_eq!(content.content_type(), ContentType::Fat16);
+        let content = probe_data(&fat(1_000_000, 8000, 0)).unwrap();
+        assert_eq!(content.content_type(), ContentType::Fat32);
+        assert_eq!(content.content_label(), Some("LABEL"));
+    }
+
+    #[test]
+    fn swap() {
+        let mut data = vec![0; 1024 * 1024];
+        put(&mut data, 4096 - 10, b"SWAPSPACE2");
+        put(&mut data, 1024 + 12, uuid().as_bytes());
+        put(&mut data, 1024 + 28, LABEL.as_bytes());
+        let content = probe_data(&data).unwrap();
+        assert_eq!(content.content_type(), ContentType::Swap);
+        assert_eq!(content.content_uuid(), Some(uuid()));
+        assert_eq!(content.content_label(), Some(LABEL));
+    }
+
+    #[test]
+    fn luks() {
+        let mut data = vec![0; 1024 * 1024];
+        put(&mut data, 0, b"LUKS\xba\xbe\x00\x02");
+        put(&mut data, 24, LABEL.as_bytes());
+        put(&mut data, 168, UUID.as_bytes());
+        let content = probe_data(&data).unwrap();
+        assert_eq!(content.content_type(), ContentType::Luks2);
+        assert_eq!(content.content_uuid(), Some(uuid()));
+        assert_eq!(content.content_label(), Some(LABEL));
+        //
+        put(&mut data, 6, &[0, 1]);
+        let content = probe_data(&data).unwrap();
+        assert_eq!(content.content_type(), ContentType::Luks1);
+        assert_eq!(content.content_uuid(), Some(uuid()));
+    }
+
+    #[test]
+    fn volume_managers() {
+        let mut data = vec![0; 1024 * 1024];
+        put(&mut data, 4096, &0xA92B_4EFCu32.to_le_bytes());
+        put(&mut data, 4096 + 4, &1u32.to_le_bytes());
+        put(&mut data, 4096 + 16, uuid().as_bytes());
+        put(&mut data, 4096 + 32, b"host:0");
+        let content = probe_data(&data).unwrap();
+        assert_eq!(content.content_type(), ContentType::MdRaid);
+        assert_eq!(content.content_uuid(), Some(uuid()));
+        assert_eq!(content.content_label(), Some("host:0"));
+        //
+        let mut data = vec![0; 1024 * 1024];
+        put(&mut data, 512, b"LABELONE");
+        put(&mut data, 512 + 24, b"LVM2 001");
+        let content = probe_data(&data).unwrap();
+        assert_eq!(content.content_type(), ContentType::Lvm2);
+        //
+        let mut data = vec![0; 1024 * 1024];
+        put(&mut data, (128 + 3) * 1024, &0x00BA_B10Cu64.to_le_bytes());
+        let content = probe_data(&data).unwrap();
+        assert_eq!(content.content_type(), ContentType::Zfs);
+    }
+
+    #[test]
+    fn others() {
+        let mut data = vec![0; 1024 * 1024];
+        put(&mut data, 32768, b"\x01CD001\x01");
+        put(&mut data, 32768 + 40, b"ISO LABEL                       ");
+        let content = probe_data(&data).unwrap();
+        assert_eq!(content.content_type(), ContentType::Iso9660);
+        assert_eq!(content.content_label(), Some("ISO LABEL"));
+        //
+        let mut data = vec![0; 1024 * 1024];
+        put(&mut data, 3, b"NTFS    ");
+        put(&mut data, 0x48, &0x0123_4567_89AB_CDEFu64.to_le_bytes());
+        let content = probe_data(&data).unwrap();
+        assert_eq!(content.content_type(), ContentType::Ntfs);
+        assert_eq!(content.content_serial(), Some(0x0123_4567_89AB_CDEF));
+        //
+        let mut data = vec![0; 1024 * 1024];
+        put(&mut data, 3, b"EXFAT   ");
+        put(&mut data, 100, &0x1234_ABCDu32.to_le_bytes());
+        let content = probe_data(&data).unwrap();
+        assert_eq!(content.content_type(), ContentType::ExFat);
+        assert_eq!(content.content_serial(), Some(0x1234_ABCD));
+    }
+
+    /// Labels with invalid UTF-8 are replaced, not dropped.
+    #[test]
+    fn invalid_label() {
+        let content = Content::new(ContentType::Ext2).label(b"a\xFFb  \0\0");
+        assert_eq!(content.content_label(), Some("a\u{FFFD}b"));
+    }
+}
@@ -0,0 +1,823 @@
+//! `sfdisk` dump format
+//!
+//! Read and write the text format used by `sfdisk --dump`, and accepted by
+//! `sfdisk` as a script, so layouts can be replayed without util-linux.
+//!
+//! ```text
+//! label: gpt
+//! label-id: 5E5D1F3B-8C2A-4E11-9F5E-6A2B0C1D2E3F
+//! device: /dev/sda
+//! unit: sectors
+//! first-lba: 34
+//! last-lba: 20446
+//! sector-size: 512
+//!
+//! /dev/sda1 : start=        2048, size=        2048, type=C12A7328-F81F-11D2-BA4B-00A0C93EC93B, name="ESP"
+//! ```
+//!
+//! With the `serde` feature, [`Json`] serializes like `sfdisk --json`.
+//!
+//! When parsing, partition `start`, `size`, `type` and `uuid` may be omitted,
+//! as with `sfdisk` scripts.
+//! The number at the end of a partition device name, such as the `3` in
+//! `/dev/sda3`, is its slot plus one, like `sfdisk`.
+//! Partitions without one use the first unused slot.
+//! Partitions are always sorted by their start.
+use crate::{
+    gpt::{
+        check_disk_size,
+        error::*,
+        header::MIN_PARTITIONS_BYTES,
+        partition::Partition,
+        slot,
+        GptHelper,
+    },
+    partitions::PartitionType,
+    types::*,
+    GptC,
+};
+use arrayvec::ArrayString;
+use core::fmt::{self, Write};
+#[cfg(feature = "serde")]
+use serde_crate::{ser::SerializeStruct, Serialize, Serializer};
+use uuid::Uuid;
+
+/// Partition attribute bit names, as used by `sfdisk`.
+const ATTRIBUTES: [(u64, &str); 3] = [
+    (0, "RequiredPartition"),
+    (1, "NoBlockIOProtocol"),
+    (2, "LegacyBIOSBootable"),
+];
+
+/// First of the partition type specific attribute bits.
+const GUID_BITS: u64 = 48;
+
+/// Alignment, in bytes, of partitions without a `start`.
+const ALIGN: u64 = 1024 * 1024;
+
+/// The `sfdisk` dump of a [`GptC`].
+///
+/// # Examples
+///
+/// ```rust
+/// # use parts::{sfdisk::Dump, Gpt, uuid::Uuid, types::*};
+/// let gpt: Gpt = Gpt::new(Uuid::nil(), Size::from_mib(10), BlockSize::new(512));
+/// let dump = Dump::new(&gpt, "/dev/sda").to_string();
+/// assert!(dump.starts_with("label: gpt\n"));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Dump<'a, C> {
+    gpt: &'a GptC<C>,
+    device: &'a str,
+}
+
+impl<'a, C: GptHelper<C>> Dump<'a, C> {
+    /// Dump `gpt`, naming the disk `device`.
+    ///
+    /// Partitions are named after `device`, like `sfdisk` does.
+    pub fn new(gpt: &'a GptC<C>, device: &'a str) -> Self {
+        Self { gpt, device }
+    }
+}
+
+impl<'a, C: GptHelper<C>> fmt::Display for Dump<'a, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let gpt = self.gpt;
+        writeln!(f, "label: gpt")?;
+        writeln!(f, "label-id: {:X}", gpt.uuid())?;
+        writeln!(f, "device: {}", self.device)?;
+        writeln!(f, "unit: sectors")?;
+        writeln!(f, "first-lba: {}", gpt.first_usable().0)?;
+        writeln!(f, "last-lba: {}", gpt.last_usable().0)?;
+        writeln!(f, "sector-size: {}", gpt.block_size().get())?;
+        writeln!(f)?;
+        for part in gpt.partitions() {
+            write!(
+                f,
+                "{} : start={:>12}, size={:>12}, type={:X}, uuid={:X}",
+                Node(self.device, slot(part) + 1),
+                part.start().0,
+                (part.end().0 - part.start().0) + 1,
+                part.partition_type().to_uuid(),
+                part.uuid(),
+            )?;
+            if !part.name().is_empty() {
+                write!(f, ", name=\"{}\"", Escaped(part.name()))?;
+            }
+            if part.attributes() != 0 {
+                write!(f, ", attrs=\"{}\"", Attributes(part.attributes()))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Serializes like `sfdisk --json`.
+///
+/// `name` and `attrs` are omitted for partitions without them,
+/// like `sfdisk` does.
+///
+/// # Examples
+///
+/// ```rust
+/// # use parts::{sfdisk::Json, Gpt, uuid::Uuid, types::*};
+/// # fn main() -> anyhow::Result<()> {
+/// let gpt: Gpt = Gpt::new(Uuid::nil(), Size::from_mib(10), BlockSize::new(512));
+/// let json = serde_json::to_string_pretty(&Json::new(&gpt, "/dev/sda"))?;
+/// assert!(json.contains(r#""label": "gpt""#));
+/// # Ok(()) }
+/// ```
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Copy)]
+pub struct Json<'a, C> {
+    gpt: &'a GptC<C>,
+    device: &'a str,
+}
+
+#[cfg(feature = "serde")]
+impl<'a, C: GptHelper<C>> Json<'a, C> {
+    /// Serialize `gpt`, naming the disk `device`.
+    ///
+    /// Partitions are named after `device`, like `sfdisk` does.
+    pub fn new(gpt: &'a GptC<C>, device: &'a str) -> Self {
+        Self { gpt, device }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'a, C: GptHelper<C>> Serialize for Json<'a, C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("Json", 1)?;
+        s.serialize_field("partitiontable", &JsonTable(self))?;
+        s.end()
+    }
+}
+
+/// The `partitiontable` object of [`Json`].
+#[cfg(feature = "serde")]
+struct JsonTable<'b, 'a, C>(&'b Json<'a, C>);
+
+#[cfg(feature = "serde")]
+impl<C: GptHelper<C>> Serialize for JsonTable<'_, '_, C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        let Json { gpt, device } = self.0;
+        let mut s = serializer.serialize_struct("PartitionTable", 8)?;
+        s.serialize_field("label", "gpt")?;
+        s.serialize_field("id", &Upper(gpt.uuid()))?;
+        s.serialize_field("device", device)?;
+        s.serialize_field("unit", "sectors")?;
+        s.serialize_field("firstlba", &gpt.first_usable().0)?;
+        s.serialize_field("lastlba", &gpt.last_usable().0)?;
+        s.serialize_field("sectorsize", &gpt.block_size().get())?;
+        s.serialize_field("partitions", &JsonPartitions(self.0))?;
+        s.end()
+    }
+}
+
+/// The `partitions` array of [`Json`].
+#[cfg(feature = "serde")]
+struct JsonPartitions<'b, 'a, C>(&'b Json<'a, C>);
+
+#[cfg(feature = "serde")]
+impl<C: GptHelper<C>> Serialize for JsonPartitions<'_, '_, C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        let Json { gpt, device } = self.0;
+        serializer.collect_seq(
+            gpt.partitions()
+                .iter()
+                .map(|part| JsonPartition(Node(device, slot(part) + 1), part)),
+        )
+    }
+}
+
+/// A partition in [`Json`].
+#[cfg(feature = "serde")]
+struct JsonPartition<'a>(Node<'a>, &'a Partition);
+
+#[cfg(feature = "serde")]
+impl Serialize for JsonPartition<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        let JsonPartition(node, part) = self;
+        let mut s = serializer.serialize_struct("Partition", 7)?;
+        s.serialize_field("node", &Collect(node))?;
+        s.serialize_field("start", &part.start().0)?;
+        s.serialize_field("size", &((part.end().0 - part.start().0) + 1))?;
+        s.serialize_field("type", &Upper(part.partition_type().to_uuid()))?;
+        s.serialize_field("uuid", &Upper(part.uuid()))?;
+        if part.name().is_empty() {
+            s.skip_field("name")?;
+        } else {
+            s.serialize_field("name", part.name())?;
+        }
+        if part.attributes() == 0 {
+            s.skip_field("attrs")?;
+        } else {
+            s.serialize_field("attrs", &Collect(Attributes(part.attributes())))?;
+        }
+        s.end()
+    }
+}
+
+/// Serialize a [`Uuid`] in upper case, like `sfdisk` does.
+#[cfg(feature = "serde")]
+struct Upper(Uuid);
+
+#[cfg(feature = "serde")]
+impl Serialize for Upper {
+    fn serialize<S: Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{:X}", self.0))
+    }
+}
+
+/// Serialize anything [`fmt::Display`] as a string.
+#[cfg(feature = "serde")]
+struct Collect<T>(T);
+
+#[cfg(feature = "serde")]
+impl<T: fmt::Display> Serialize for Collect<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0)
+    }
+}
+
+/// Device name of partition `number`, from the disk device name.
+///
+/// Like the kernel, a `p` separates them if the disk ends with a digit.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Node<'a>(pub &'a str, pub usize);
+
+impl fmt::Display for Node<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sep = match self.0.bytes().last() {
+            Some(b) if b.is_ascii_digit() => "p",
+            _ => "",
+        };
+        write!(f, "{}{}{}", self.0, sep, self.1)
+    }
+}
+
+/// Partition attributes in `sfdisk` syntax.
+///
+/// Bits without a name, outside the partition type specific range,
+/// are written as plain numbers.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Attributes(pub u64);
+
+impl fmt::Display for Attributes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sep = "";
+        for bit in 0..GUID_BITS {
+            if self.0 & (1 << bit) == 0 {
+                continue;
+            }
+            match ATTRIBUTES.iter().find(|(b, _)| *b == bit) {
+                Some((_, name)) => write!(f, "{}{}", sep, name)?,
+                None => write!(f, "{}{}", sep, bit)?,
+            }
+            sep = " ";
+        }
+        let mut guid = "GUID:";
+        for bit in GUID_BITS..64 {
+            if self.0 & (1 << bit) != 0 {
+                write!(f, "{}{}{}", sep, guid, bit)?;
+                sep = ",";
+                guid = "";
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Partition name with quotes, backslashes and control characters escaped
+/// as `\xHH`, like `sfdisk` does.
+struct Escaped<'a>(&'a str);
+
+impl fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '"' | '\\' => write!(f, "\\x{:02x}", c as u32)?,
+                c if c.is_ascii_control() => write!(f, "\\x{:02x}", c as u32)?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Header values collected before the first partition.
+struct Headers {
+    uuid: Option<Uuid>,
+    last_lba: Option<(usize, u64)>,
+    block_size: BlockSize,
+}
+
+/// Parse a `sfdisk` dump or script.
+///
+/// `disk_size` is required if the dump has no `last-lba`,
+/// otherwise it must agree with it.
+///
+/// Without `alloc`, every partition and the disk need a `uuid`/`label-id`.
+///
+/// # Errors
+///
+/// - [`Error::Parse`] if `dump` can't be parsed, or a partition is invalid.
+/// - [`Error::Invalid`] if the disk size is unknown, or the disk has no
+///   `label-id` without `alloc`.
+///
+/// # Examples
+///
+/// ```rust
+/// # use parts::{sfdisk, Gpt};
+/// let gpt: Gpt = sfdisk::parse(
+///     "label: gpt
+///      label-id: 1E7D1A6C-3D5B-4C4A-9E3B-2F1C0A9B8D7E
+///      last-lba: 20446
+///
+///      start=2048, size=2048, type=U, uuid=7A1B2C3D-4E5F-4A6B-8C7D-9E0F1A2B3C4D, name=\"ESP\"
+///      type=L, uuid=0F1E2D3C-4B5A-4968-8776-655443322110, name=\"Root\"",
+///     None,
+/// )
+/// .unwrap();
+/// assert_eq!(gpt.partitions().len(), 2);
+/// ```
+pub fn parse<C: GptHelper<C>>(dump: &str, disk_size: Option<Size>) -> Result<GptC<C>> {
+    let mut headers = Headers {
+        uuid: None,
+        last_lba: None,
+        block_size: BlockSize::new(512),
+    };
+    let mut gpt = None;
+    for (line, text) in dump.lines().enumerate() {
+        let line = line + 1;
+        let text = text.trim();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        let err = |msg| Error::Parse { line, msg };
+        // Partition lines are `[node :] key=value, ...`, headers `key: value`
+        let (node, fields) = match (text.find(':'), text.find('=')) {
+            (Some(colon), eq) if eq.is_none_or(|eq| colon < eq) => {
+                let (key, value) = (text[..colon].trim(), text[colon + 1..].trim());
+                if value.contains('=') {
+                    (Some(key), value)
+                } else {
+                    if gpt.is_some() {
+                        return Err(err("Header after partitions"));
+                    }
+                    header(&mut headers, key, value, line).map_err(err)?;
+                    continue;
+                }
+            }
+            (_, Some(_)) => (None, text),
+            _ => return Err(err("Expected a header or partition")),
+        };
+        let gpt = match &mut gpt {
+            Some(gpt) => gpt,
+            None => gpt.get_or_insert(new_gpt(&headers, disk_size)?),
+        };
+        partition(gpt, node, fields, line)?;
+    }
+    match gpt {
+        Some(gpt) => Ok(gpt),
+        None => new_gpt(&headers, disk_size),
+    }
+}
+
+/// Parse the header `key` with `value`, from `line`, into `headers`.
+fn header(
+    headers: &mut Headers,
+    key: &str,
+    value: &str,
+    line: usize,
+) -> core::result::Result<(), &'static str> {
+    let number = || value.parse::<u64>().map_err(|_| "Invalid number");
+    match key {
+        "label" if value == "gpt" => (),
+        "label" => return Err("Only gpt labels are supported"),
+        "label-id" => headers.uuid = Some(Uuid::parse_str(value).map_err(|_| "Invalid label-id")?),
+        "unit" if value == "sectors" => (),
+        "unit" => return Err("Only sectors are supported"),
+        "sector-size" => {
+            let size = number()?;
+            if size < 512 || !size.is_power_of_two() {
+                return Err("Invalid sector-size");
+            }
+            headers.block_size = BlockSize::new(size);
+        }
+        "last-lba" => headers.last_lba = Some((line, number()?)),
+        // The first usable block is fixed by the partition array size.
+        "first-lba" => {
+            number()?;
+        }
+        "table-length" if number()? <= 128 => (),
+        "table-length" => return Err("Unsupported table-length"),
+        "device" | "grain" => (),
+        _ => return Err("Unknown header"),
+    }
+    Ok(())
+}
+
+/// Create the empty [`GptC`] described by `headers`.
+fn new_gpt<C: GptHelper<C>>(headers: &Headers, disk_size: Option<Size>) -> Result<GptC<C>> {
+    let block_size = headers.block_size;
+    // Primary header and array before, backup header and array after.
+    let array = (MIN_PARTITIONS_BYTES / block_size).0;
+    let disk_size = match (headers.last_lba, disk_size) {
+        (_, Some(size)) => size,
+        (Some((_, last)), None) => Size::from_bytes((last + array + 2) * block_size.get()),
+        (None, None) => return Err(Error::Invalid("Missing last-lba, and no disk size")),
+    };
+    check_disk_size(block_size, disk_size)?;
+    let uuid = match headers.uuid {
+        Some(uuid) => uuid,
+        #[cfg(feature = "alloc")]
+        None => Uuid::new_v4(),
+        #[cfg(not(feature = "alloc"))]
+        None => return Err(Error::Invalid("Missing label-id")),
+    };
+    let gpt = GptC::new(uuid, disk_size, block_size);
+    if let Some((line, last)) = headers.last_lba {
+        if gpt.last_usable().0 != last {
+            return Err(Error::Parse {
+                line,
+                msg: "last-lba does not match the disk size",
+            });
+        }
+    }
+    Ok(gpt)
+}
+
+/// Parse a partition from `fields`, in the slot from its device name `node`,
+/// and add it to `gpt`.
+fn partition<C: GptHelper<C>>(
+    gpt: &mut GptC<C>,
+    node: Option<&str>,
+    fields: &str,
+    line: usize,
+) -> Result<()> {
+    let err = |msg| Error::Parse { line, msg };
+    let block_size = gpt.block_size();
+    let mut start = None;
+    let mut size = None;
+    let mut part = Partition::new();
+    if let Some(node) = node {
+        let number = &node[node.trim_end_matches(|c: char| c.is_ascii_digit()).len()..];
+        match number.parse::<usize>() {
+            Ok(number) if number > 0 => part.set_slot(Some(number - 1)),
+            _ => return Err(err("Invalid partition device name")),
+        }
+    }
+    part.set_partition_type(PartitionType::LinuxFilesystemData);
+    let mut uuid = None;
+    let mut fields = Fields(fields);
+    while let Some((key, value)) = fields.next().transpose().map_err(err)? {
+        match key {
+            "start" => start = Some(blocks(value, block_size).ok_or_else(|| err("Invalid start"))?),
+            "size" if value == "+" => size = None,
+            "size" => size = Some(blocks(value, block_size).ok_or_else(|| err("Invalid size"))?),
+            "type" => {
+                let p_type = partition_type(value).ok_or_else(|| err("Invalid type"))?;
+                part.set_partition_type(p_type);
+            }
+            "uuid" => uuid = Some(Uuid::parse_str(value).map_err(|_| err("Invalid uuid"))?),
+            "name" => part.set_name(&unescape(value).ok_or_else(|| err("Invalid name"))?),
+            "attrs" => part.set_attributes(attributes(value).ok_or_else(|| err("Invalid attrs"))?),
+            _ => return Err(err("Unknown partition field")),
+        }
+    }
+    if part.partition_type() == PartitionType::Unused {
+        return Err(err("Partition type is unused"));
+    }
+    part.set_uuid(match uuid {
+        Some(uuid) => uuid,
+        #[cfg(feature = "alloc")]
+        None => Uuid::new_v4(),
+        #[cfg(not(feature = "alloc"))]
+        None => return Err(err("Missing uuid")),
+    });
+    if gpt.find_by_uuid(part.uuid()).is_some() {
+        return Err(err("Partition uuid is not unique"));
+    }
+    // Like sfdisk, default to the first aligned block after all partitions,
+    // and all space until the next partition.
+    let start = match start {
+        Some(start) => Block(start),
+        None => {
+            let align = (ALIGN / block_size.get()).max(1);
+            Block(gpt.next_usable().0.div_ceil(align) * align)
+        }
+    };
+    let end = match size {
+        Some(0) => return Err(err("Invalid size")),
+        Some(size) => start + (size - 1),
+        None => gpt
+            .partitions()
+            .iter()
+            .map(|p| p.start())
+            .filter(|s| *s > start)
+            .min()
+            .map_or_else(|| gpt.last_usable(), |s| s - 1),
+    };
+    part.set_range(start, end);
+    gpt.check_partition(&part, usize::MAX)
+        .map_err(|e| match e {
+            Error::Overlap => err("Partition overlaps another or is outside the usable range"),
+            _ => err("Partition ends before it starts"),
+        })?;
+    gpt.add_partition(part).map_err(|e| match e {
+        Error::InvalidPartition(msg) => err(msg),
+        _ => err("Too many partitions"),
+    })
+}
+
+/// `key=value` fields, separated by commas and/or whitespace.
+///
+/// Values may be quoted.
+struct Fields<'a>(&'a str);
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = core::result::Result<(&'a str, &'a str), &'static str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = self
+            .0
+            .trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+        if rest.is_empty() {
+            return None;
+        }
+        let eq = match rest.find('=') {
+            Some(eq) => eq,
+            None => return Some(Err("Expected key=value")),
+        };
+        let key = rest[..eq].trim();
+        let rest = rest[eq + 1..].trim_start();
+        let (value, rest) = if let Some(quoted) = rest.strip_prefix('"') {
+            match quoted.find('"') {
+                Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                None => return Some(Err("Unterminated quote")),
+            }
+        } else {
+            let end = rest
+                .find(|c: char| c == ',' || c.is_whitespace())
+                .unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        };
+        self.0 = rest;
+        Some(Ok((key, value)))
+    }
+}
+
+/// Parse a number of blocks, or a size in bytes with a `K`, `M`, `G`, `T` or
+/// `P` suffix, optionally followed by `iB`, rounded up to blocks.
+fn blocks(value: &str, block_size: BlockSize) -> Option<u64> {
+    let value = value.strip_prefix('+').unwrap_or(value);
+    let value = value.strip_suffix("iB").unwrap_or(value);
+    let digits = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let number: u64 = value[..digits].parse().ok()?;
+    let shift = match &value[digits..] {
+        "" => return Some(number),
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        "P" => 50,
+        _ => return None,
+    };
+    let bytes = number.checked_mul(1 << shift)?;
+    Some(bytes.div_ceil(block_size.get()))
+}
+
+/// Parse a partition type GUID, or one of the `sfdisk` GPT aliases.
+fn partition_type(value: &str) -> Option<PartitionType> {
+    let uuid = match value {
+        "L" | "linux" => return Some(PartitionType::LinuxFilesystemData),
+        "S" | "swap" => return Some(PartitionType::LinuxSwap),
+        "U" | "uefi" => return Some(PartitionType::EfiSystem),
+        "V" | "lvm" => return Some(PartitionType::LinuxLvm),
+        "H" | "home" => "933AC7E1-2EB4-4F13-B844-0E14E2AEF915",
+        "R" | "raid" => "A19D880F-05FC-4D3B-A006-743F0F84911E",
+        uuid => uuid,
+    };
+    Uuid::parse_str(uuid).ok().map(PartitionType::from_uuid)
+}
+
+/// Undo [`Escaped`]. Fails if the name is more than 70 bytes.
+fn unescape(value: &str) -> Option<ArrayString<[u8; 72]>> {
+    let mut name = ArrayString::new();
+    let mut rest = value;
+    while let Some(i) = rest.find("\\x") {
+        name.try_push_str(&rest[..i]).ok()?;
+        let byte = u8::from_str_radix(rest.get(i + 2..i + 4)?, 16).ok()?;
+        name.try_push(char::from(byte)).ok()?;
+        rest = &rest[i + 4..];
+    }
+    name.try_push_str(rest).ok()?;
+    if name.len() > 70 {
+        return None;
+    }
+    Some(name)
+}
+
+/// Parse partition attributes in `sfdisk` syntax.
+fn attributes(value: &str) -> Option<u64> {
+    let mut attrs = 0;
+    for word in value.split_whitespace() {
+        if let Some(bits) = word.strip_prefix("GUID:") {
+            for bit in bits.split(',') {
+                let bit: u64 = bit.parse().ok()?;
+                if !(GUID_BITS..64).contains(&bit) {
+                    return None;
+                }
+                attrs |= 1 << bit;
+            }
+        } else if let Some((bit, _)) = ATTRIBUTES.iter().find(|(_, name)| *name == word) {
+            attrs |= 1 << bit;
+        } else {
+            let bit: u64 = word.parse().ok()?;
+            if bit >= 64 {
+                return None;
+            }
+            attrs |= 1 << bit;
+        }
+    }
+    Some(attrs)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{
+        util::{Result, BLOCK_SIZE},
+        Gpt,
+        PartitionBuilder,
+    };
+    use pretty_assertions::assert_eq;
+
+    const DUMP: &str = r#"label: gpt
+label-id: 00000000-0000-0000-0000-000000000001
+device: /dev/sda
+unit: sectors
+first-lba: 34
+last-lba: 20446
+sector-size: 512
+
+/dev/sda1 : start=        2048, size=        2048, type=C12A7328-F81F-11D2-BA4B-00A0C93EC93B, uuid=00000000-0000-0000-0000-000000000002, name="ESP \x22boot\x22"
+/dev/sda2 : start=        4096, size=        4096, type=0FC63DAF-8483-4772-8E79-3D69D8477DE4, uuid=00000000-0000-0000-0000-000000000003, attrs="RequiredPartition LegacyBIOSBootable 10 GUID:48,63"
+"#;
+
+    fn gpt() -> Result<Gpt> {
+        let mut gpt: Gpt = Gpt::new(Uuid::from_u128(1), Size::from_mib(10), BLOCK_SIZE);
+        let esp = PartitionBuilder::new(Uuid::from_u128(2), &gpt)
+            .start(Block(2048))
+            .size(Size::from_mib(1))
+            .partition_type(PartitionType::EfiSystem)
+            .name("ESP \"boot\"")
+            .finish();
+        let root = PartitionBuilder::new(Uuid::from_u128(3), &gpt)
+            .start(Block(4096))
+            .size(Size::from_mib(2))
+            .partition_type(PartitionType::LinuxFilesystemData)
+            .attributes((1 << 0) | (1 << 2) | (1 << 10) | (1 << 48) | (1 << 63))
+            .finish();
+        gpt.add_partition(esp)?;
+        gpt.add_partition(root)?;
+        Ok(gpt)
+    }
+
+    #[test]
+    fn write() -> Result {
+        assert_eq!(Dump::new(&gpt()?, "/dev/sda").to_string(), DUMP);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn json() -> Result {
+        let json = serde_json::to_value(Json::new(&gpt()?, "/dev/nvme0n1"))?;
+        let expected = serde_json::json!({
+            "partitiontable": {
+                "label": "gpt",
+                "id": "00000000-0000-0000-0000-000000000001",
+                "device": "/dev/nvme0n1",
+                "unit": "sectors",
+                "firstlba": 34,
+                "lastlba": 20446,
+                "sectorsize": 512,
+                "partitions": [
+                    {
+                        "node": "/dev/nvme0n1p1",
+                        "start": 2048,
+                        "size": 2048,
+                        "type": "C12A7328-F81F-11D2-BA4B-00A0C93EC93B",
+                        "uuid": "00000000-0000-0000-0000-000000000002",
+                        "name": "ESP \"boot\""
+                    },
+                    {
+                        "node": "/dev/nvme0n1p2",
+                        "start": 4096,
+                        "size": 4096,
+                        "type": "0FC63DAF-8483-4772-8E79-3D69D8477DE4",
+                        "uuid": "00000000-0000-0000-0000-000000000003",
+                        "attrs": "RequiredPartition LegacyBIOSBootable 10 GUID:48,63"
+                    }
+                ]
+            }
+        });
+        assert_eq!(json, expected);
+        // Nodes follow the slot, not the position
+        let mut gpt = gpt()?;
+        gpt.remove_partition(0);
+        let json = serde_json::to_value(Json::new(&gpt, "/dev/sda"))?;
+        assert_eq!(json["partitiontable"]["partitions"][0]["node"], "/dev/sda2");
+        Ok(())
+    }
+
+    /// Device names follow the slot, not the position
+    #[test]
+    fn slots() -> Result {
+        let mut gpt = gpt()?;
+        gpt.remove_partition(0);
+        let dump = Dump::new(&gpt, "/dev/sda").to_string();
+        assert!(
+            dump.contains("\n/dev/sda2 : start=        4096"),
+            "{}",
+            dump
+        );
+        let parsed: Gpt = parse(&dump, None)?;
+        assert_eq!(parsed, gpt);
+        assert_eq!(parsed.partitions()[0].slot(), Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn read() -> Result {
+        let parsed: Gpt = parse(DUMP, None)?;
+        assert_eq!(parsed, gpt()?);
+        let parsed: Gpt = parse(DUMP, Some(Size::from_mib(10)))?;
+        assert_eq!(parsed, gpt()?);
+        Ok(())
+    }
+
+    /// sfdisk scripts can leave most things out
+    #[test]
+    fn script() -> Result {
+        let script = "# Comment\nstart=2048 size=1M type=U\n\ntype=S, size=+2MiB\nname=Rest";
+        let gpt: Gpt = parse(script, Some(Size::from_mib(10)))?;
+        let parts = gpt.partitions();
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0].partition_type(), PartitionType::EfiSystem);
+        assert_eq!(
+            (parts[0].start(), parts[0].end()),
+            (Block(2048), Block(4095))
+        );
+        assert_eq!(parts[1].partition_type(), PartitionType::LinuxSwap);
+        assert_eq!(
+            (parts[1].start(), parts[1].end()),
+            (Block(4096), Block(8191))
+        );
+        assert_eq!(parts[2].name(), "Rest");
+        assert_eq!(
+            parts[2].partition_type(),
+            PartitionType::LinuxFilesystemData
+        );
+        assert_eq!(
+            (parts[2].start(), parts[2].end()),
+            (Block(8192), gpt.last_usable())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        let cases: &[(&str, usize)] = &[
+            ("label: dos", 1),
+            ("unit: bytes", 1),
+            ("last-lba: 20446\nbogus: 1", 2),
+            ("last-lba: 10", 0),
+            ("", 0),
+            ("last-lba: 20446\nstart=1, size=1", 2),
+            ("last-lba: 20446\nstart=2048, size=4096\nstart=4096", 3),
+            ("last-lba: 20446\nstart=2048 bootable=1", 2),
+            ("last-lba: 20446\nstart=2048 name=\"unterminated", 2),
+            ("last-lba: 20446\nstart=2048 attrs=\"GUID:3\"", 2),
+            ("last-lba: 20446\nstart=2048\nlabel: gpt", 3),
+            ("last-lba: 20446\n/dev/sda : start=2048", 2),
+            ("last-lba: 20446\nsda1 : start=2048\nsda1 : start=4096", 3),
+        ];
+        for (dump, line) in cases {
+            match parse::<Vec<Partition>>(dump, None) {
+                Err(Error::Parse { line: l, .. }) if l == *line => (),
+                Err(Error::Invalid(_)) if *line == 0 => (),
+                e => panic!("Invalid dump {:?} wasn't rejected properly: {:?}", dump, e),
+            }
+        }
+        match parse::<Vec<Partition>>(DUMP, Some(Size::from_mib(20))) {
+            Err(Error::Parse { line: 6, .. }) => (),
+            e => panic!("Mismatched disk size was accepted: {:?}", e),
+        }
+    }
+}
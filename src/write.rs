@@ -0,0 +1,67 @@
+//! Describing how a GPT is written to disk
+//!
+//! See [`GptC::write_plan`] to find out exactly which byte ranges writing a
+//! [`GptC`] would touch, without touching the device.
+//!
+//! [`GptC`]: crate::GptC
+//! [`GptC::write_plan`]: crate::GptC::write_plan
+use crate::types::*;
+use core::fmt;
+
+/// A region of the disk written as part of a GPT.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Region {
+    /// The protective MBR, in the first block.
+    Mbr,
+
+    /// The backup header, in the last block.
+    BackupHeader,
+
+    /// The backup partition array, just before the backup header.
+    BackupArray,
+
+    /// The primary header, in the second block.
+    PrimaryHeader,
+
+    /// The primary partition array, just after the primary header.
+    PrimaryArray,
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Region::Mbr => "Protective MBR",
+            Region::BackupHeader => "Backup GPT header",
+            Region::BackupArray => "Backup partition array",
+            Region::PrimaryHeader => "Primary GPT header",
+            Region::PrimaryArray => "Primary partition array",
+        })
+    }
+}
+
+/// A single write, `len` bytes at `offset`, of `region`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct PlannedWrite {
+    /// What is being written
+    pub region: Region,
+
+    /// Byte offset on the device
+    pub offset: Offset,
+
+    /// Number of bytes
+    pub len: Size,
+}
+
+/// `len` bytes at `offset`: `region`
+impl fmt::Display for PlannedWrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes at {}: {}",
+            self.len.as_bytes(),
+            self.offset.0,
+            self.region
+        )
+    }
+}